@@ -44,6 +44,7 @@ use std::str::FromStr;
 
 #[repr(u32)]
 #[derive(Default, Copy, Clone, Debug, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Supported image formats for textures.
 pub enum ImageFormat {
     #[default]
@@ -90,6 +91,7 @@ pub enum ImageFormat {
 
 #[repr(i32)]
 #[derive(Copy, Clone, Default, Debug, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// The filtering mode for a texture sampler.
 pub enum FilterMode {
     /// Linear filtering.
@@ -128,6 +130,7 @@ impl FromStr for FilterMode {
 
 #[repr(i32)]
 #[derive(Copy, Clone, Default, Debug, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// The wrapping (address) mode for a texture sampler.
 pub enum WrapMode {
     #[default]
@@ -0,0 +1,384 @@
+//! Bundling of shader presets with the files they depend on.
+//!
+//! [`export_preset`] copies a parsed preset and the shader sources, includes, and LUT
+//! textures it uses into a self-contained directory.
+//!
+//! With the `archive` feature, [`pack_preset`] packs a preset on disk, its `#reference`
+//! chain, the shader sources and includes of every pass, and every LUT texture into a single
+//! zip or tar bundle. Bundles can then be loaded like any other preset by parsing the path to
+//! the archive.
+use crate::{BundleError, ShaderSource};
+use librashader_presets::fs::{absolute_path, DiskFileSystem};
+use librashader_presets::{BuiltinShader, PresetFileSystem, ShaderPreset};
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+
+#[cfg(feature = "archive")]
+use librashader_presets::fs::{ArchiveFormat, MemoryFileSystem};
+#[cfg(feature = "archive")]
+use librashader_presets::WildcardContext;
+#[cfg(feature = "archive")]
+use std::io::{Seek, Write};
+
+/// A filesystem that remembers the contents of every file read through it.
+struct RecordingFileSystem<'a> {
+    fs: &'a dyn PresetFileSystem,
+    files: RefCell<Vec<(PathBuf, Vec<u8>)>>,
+}
+
+impl PresetFileSystem for RecordingFileSystem<'_> {
+    fn read(&self, path: &Path) -> std::io::Result<Vec<u8>> {
+        let contents = self.fs.read(path)?;
+        let path = absolute_path(path);
+        let mut files = self.files.borrow_mut();
+        if !files.iter().any(|(file, _)| *file == path) {
+            files.push((path, contents.clone()));
+        }
+        Ok(contents)
+    }
+
+    fn canonicalize(&self, path: &Path) -> std::io::Result<PathBuf> {
+        self.fs.canonicalize(path)
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        self.fs.exists(path)
+    }
+}
+
+impl<'a> RecordingFileSystem<'a> {
+    fn new(fs: &'a dyn PresetFileSystem) -> Self {
+        RecordingFileSystem {
+            fs,
+            files: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Read the shader sources, includes, and textures used by a parsed preset.
+    fn read_dependencies(&self, preset: &ShaderPreset) -> Result<(), BundleError> {
+        for shader in &preset.shaders {
+            ShaderSource::load_with_resolver(&shader.name, self)?;
+        }
+        for texture in &preset.textures {
+            self.read(&texture.path)
+                .map_err(|e| BundleError::IOError(texture.path.clone(), e))?;
+        }
+        Ok(())
+    }
+}
+
+/// Every file needed to load a shader preset, with absolute paths.
+#[cfg(feature = "archive")]
+pub(crate) struct PresetFiles {
+    /// The path of the root preset.
+    pub root: PathBuf,
+    /// The paths and contents of every file, in the order they were first read.
+    pub files: Vec<(PathBuf, Vec<u8>)>,
+}
+
+/// Read every file the shader preset at the given path depends on.
+#[cfg(feature = "archive")]
+pub(crate) fn collect_preset_files(
+    path: &Path,
+    context: WildcardContext,
+    fs: &dyn PresetFileSystem,
+) -> Result<PresetFiles, BundleError> {
+    let recorder = RecordingFileSystem::new(fs);
+    let preset = ShaderPreset::try_parse_with_fs(path, context, &recorder)?;
+    recorder.read_dependencies(&preset)?;
+
+    let files = recorder.files.into_inner();
+    // The root preset is always the first file read by the parser.
+    let Some(root) = files
+        .iter()
+        .map(|(path, _)| path)
+        .find(|path| path.extension().is_some_and(|ext| ext == "slangp"))
+        .cloned()
+    else {
+        return Err(BundleError::IOError(
+            path.to_path_buf(),
+            std::io::ErrorKind::NotFound.into(),
+        ));
+    };
+
+    Ok(PresetFiles { root, files })
+}
+
+/// Find the deepest directory that contains every file.
+pub(crate) fn common_root<'a>(mut paths: impl Iterator<Item = &'a Path>) -> Option<PathBuf> {
+    let mut root = paths.next()?.parent()?.to_path_buf();
+    for path in paths {
+        while !path.starts_with(&root) {
+            if !root.pop() {
+                return None;
+            }
+        }
+    }
+    Some(root)
+}
+
+/// Pack the shader preset at the given path, and every file it depends on, into a zip or
+/// tar bundle.
+///
+/// Files keep their layout relative to each other within the bundle. If the preset would
+/// not be the single top-level preset of the bundle, the files are placed in a directory
+/// named after the preset, next to a top-level preset that references it.
+#[cfg(feature = "archive")]
+pub fn pack_preset(
+    path: impl AsRef<Path>,
+    context: WildcardContext,
+    format: ArchiveFormat,
+    writer: impl Write + Seek,
+) -> Result<(), BundleError> {
+    let path = path.as_ref();
+    let PresetFiles { root, files } = collect_preset_files(path, context, &DiskFileSystem)?;
+
+    let invalid_root = || {
+        BundleError::IOError(
+            path.to_path_buf(),
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "the preset depends on files that do not share a common root",
+            ),
+        )
+    };
+    let base =
+        common_root(files.iter().map(|(path, _)| path.as_path())).ok_or_else(invalid_root)?;
+    let relative_root = root.strip_prefix(&base).map_err(|_| invalid_root())?;
+
+    let mut bundle = MemoryFileSystem::new();
+    for (path, contents) in &files {
+        bundle.insert(
+            path.strip_prefix(&base).map_err(|_| invalid_root())?,
+            contents.as_slice(),
+        );
+    }
+
+    if bundle.root_preset() != Some(&Path::new("/").join(relative_root)) {
+        let name = root.file_stem().unwrap_or_default().to_string_lossy();
+        let mut nested = MemoryFileSystem::new();
+        for (path, contents) in &files {
+            let relative = path.strip_prefix(&base).map_err(|_| invalid_root())?;
+            nested.insert(Path::new(name.as_ref()).join(relative), contents.as_slice());
+        }
+
+        let reference = format!(
+            "{name}/{}",
+            relative_root.to_string_lossy().replace('\\', "/")
+        );
+        nested.insert(
+            format!("{name}.slangp"),
+            format!("#reference \"{reference}\"\n"),
+        );
+        bundle = nested;
+    }
+
+    bundle
+        .write_archive(format, writer)
+        .map_err(|e| BundleError::IOError(path.to_path_buf(), e))
+}
+
+/// Export a shader preset into a self-contained directory.
+///
+/// Every shader source, included file, and LUT texture used by the preset is copied next to
+/// the preset written at `path`, keeping their layout relative to each other. The written
+/// preset refers to the copies with relative paths, so the directory can be moved or shared
+/// as a whole.
+///
+/// Presets referenced with `#reference` are already merged into a parsed preset, so the
+/// exported preset is flattened into a single `.slangp` that re-parses to the same chain.
+pub fn export_preset(preset: &ShaderPreset, path: impl AsRef<Path>) -> Result<(), BundleError> {
+    let path = absolute_path(path.as_ref());
+    let recorder = RecordingFileSystem::new(&DiskFileSystem);
+    recorder.read_dependencies(preset)?;
+    let files = recorder.files.into_inner();
+
+    let Some(base) = common_root(files.iter().map(|(path, _)| path.as_path())) else {
+        return Err(BundleError::IOError(
+            path,
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "the preset depends on files that do not share a common root",
+            ),
+        ));
+    };
+    let dir = path.parent().unwrap_or(&path);
+    // NOPANIC: every file is within the common root.
+    let exported_path = |file: &Path| dir.join(absolute_path(file).strip_prefix(&base).unwrap());
+
+    for (file, contents) in &files {
+        let exported = exported_path(file);
+        if let Some(parent) = exported.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| BundleError::IOError(parent.to_path_buf(), e))?;
+        }
+        std::fs::write(&exported, contents).map_err(|e| BundleError::IOError(exported, e))?;
+    }
+
+    let mut exported = preset.clone();
+    for shader in &mut exported.shaders {
+        if BuiltinShader::from_path(&shader.name).is_none() {
+            shader.name = exported_path(&shader.name);
+        }
+    }
+    for texture in &mut exported.textures {
+        texture.path = exported_path(&texture.path);
+    }
+
+    exported
+        .save(&path)
+        .map_err(|e| BundleError::IOError(path.clone(), e))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::bundle::export_preset;
+    use crate::ShaderSource;
+    use librashader_presets::ShaderPreset;
+    use std::path::PathBuf;
+
+    #[cfg(feature = "archive")]
+    use crate::bundle::pack_preset;
+    #[cfg(feature = "archive")]
+    use librashader_presets::fs::{ArchiveFormat, MemoryFileSystem};
+    #[cfg(feature = "archive")]
+    use librashader_presets::WildcardContext;
+    #[cfg(feature = "archive")]
+    use std::io::Cursor;
+    #[cfg(feature = "archive")]
+    use std::path::Path;
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("librashader-preprocess-{name}"));
+        std::fs::create_dir_all(dir.join("presets")).unwrap();
+        std::fs::create_dir_all(dir.join("shaders")).unwrap();
+        std::fs::create_dir_all(dir.join("include")).unwrap();
+
+        let shader = std::fs::read_to_string("../test/basic.slang").unwrap();
+        let shader = shader.replacen(
+            "#pragma name StockShader",
+            "#include \"../include/common.inc\"\n#pragma name StockShader",
+            1,
+        );
+        std::fs::write(dir.join("shaders/basic.slang"), shader).unwrap();
+        std::fs::write(dir.join("include/common.inc"), "#define COMMON 1\n").unwrap();
+        std::fs::copy("../test/sf2.png", dir.join("shaders/sf2.png")).unwrap();
+        std::fs::write(
+            dir.join("presets/base.slangp"),
+            "shaders = 1\nshader0 = ../shaders/basic.slang\n\
+             textures = LUT\nLUT = ../shaders/sf2.png\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("presets/override.slangp"),
+            "#reference \"base.slangp\"\nColorMod = 0.5\n",
+        )
+        .unwrap();
+        dir.canonicalize().unwrap()
+    }
+
+    #[cfg(feature = "archive")]
+    fn bundle_paths(bytes: &[u8], format: ArchiveFormat) -> Vec<PathBuf> {
+        let bundle = MemoryFileSystem::from_archive(bytes, format).unwrap();
+        let mut paths: Vec<PathBuf> = bundle.paths().map(Path::to_path_buf).collect();
+        paths.sort();
+        paths
+    }
+
+    #[test]
+    #[cfg(feature = "archive")]
+    fn packs_preset_and_dependencies() {
+        let dir = test_dir("pack");
+        let mut bytes = Cursor::new(Vec::new());
+        pack_preset(
+            dir.join("presets/base.slangp"),
+            WildcardContext::new(),
+            ArchiveFormat::Zip,
+            &mut bytes,
+        )
+        .unwrap();
+
+        assert_eq!(
+            vec![
+                PathBuf::from("/include/common.inc"),
+                PathBuf::from("/presets/base.slangp"),
+                PathBuf::from("/shaders/basic.slang"),
+                PathBuf::from("/shaders/sf2.png"),
+            ],
+            bundle_paths(bytes.get_ref(), ArchiveFormat::Zip)
+        );
+
+        let packed = dir.join("base.zip");
+        std::fs::write(&packed, bytes.into_inner()).unwrap();
+        let preset = ShaderPreset::try_parse(&packed).unwrap();
+        let source = ShaderSource::load(&preset.shaders[0].name).unwrap();
+        assert!(source.fragment.contains("#define COMMON 1"));
+    }
+
+    #[test]
+    #[cfg(feature = "archive")]
+    fn packs_nested_preset_behind_reference() {
+        let dir = test_dir("pack-nested");
+        let mut bytes = Cursor::new(Vec::new());
+        pack_preset(
+            dir.join("presets/override.slangp"),
+            WildcardContext::new(),
+            ArchiveFormat::Tar,
+            &mut bytes,
+        )
+        .unwrap();
+
+        assert_eq!(
+            vec![
+                PathBuf::from("/override/include/common.inc"),
+                PathBuf::from("/override/presets/base.slangp"),
+                PathBuf::from("/override/presets/override.slangp"),
+                PathBuf::from("/override/shaders/basic.slang"),
+                PathBuf::from("/override/shaders/sf2.png"),
+                PathBuf::from("/override.slangp"),
+            ],
+            bundle_paths(bytes.get_ref(), ArchiveFormat::Tar)
+        );
+
+        let packed = dir.join("override.tar");
+        std::fs::write(&packed, bytes.into_inner()).unwrap();
+        let preset = ShaderPreset::try_parse(&packed).unwrap();
+        assert_eq!(0.5, preset.parameters[0].value);
+        assert!(preset.textures[0].path.starts_with(&packed));
+        ShaderSource::load(&preset.shaders[0].name).unwrap();
+    }
+
+    #[test]
+    fn exports_self_contained_directory() {
+        let dir = test_dir("export");
+        let preset = ShaderPreset::try_parse(dir.join("presets/override.slangp")).unwrap();
+
+        let out = std::env::temp_dir().join("librashader-preprocess-exported");
+        let _ = std::fs::remove_dir_all(&out);
+        std::fs::create_dir_all(&out).unwrap();
+        let out = out.canonicalize().unwrap();
+        export_preset(&preset, out.join("exported.slangp")).unwrap();
+
+        for file in [
+            "include/common.inc",
+            "shaders/basic.slang",
+            "shaders/sf2.png",
+        ] {
+            assert!(out.join(file).is_file(), "{file} was not exported");
+        }
+        assert!(!out.join("presets").exists());
+
+        let exported = ShaderPreset::try_parse(out.join("exported.slangp")).unwrap();
+        let mut expected = preset.clone();
+        expected.shaders[0].name = out.join("shaders/basic.slang");
+        expected.textures[0].path = out.join("shaders/sf2.png");
+        assert_eq!(expected, exported);
+
+        let source = ShaderSource::load(&exported.shaders[0].name).unwrap();
+        assert_eq!(ShaderSource::load(&preset.shaders[0].name).unwrap(), source);
+        assert_eq!(
+            Some((exported.shaders[0].name.as_path(), 1)),
+            source.vertex_map.lookup(1)
+        );
+    }
+}
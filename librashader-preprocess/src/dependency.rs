@@ -0,0 +1,133 @@
+use crate::include::{read_source, IncludeResolver};
+use crate::{BundleError, PreprocessError};
+use librashader_presets::fs::normalize_path;
+use librashader_presets::{
+    Dependency, DependencyGraph, DependencyKind, PresetFileSystem, ShaderPreset, WildcardContext,
+};
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+
+/// An include resolver that remembers every include it resolved.
+struct IncludeRecorder<'a> {
+    resolver: &'a dyn IncludeResolver,
+    includes: RefCell<Vec<Dependency>>,
+}
+
+impl IncludeResolver for IncludeRecorder<'_> {
+    fn resolve(&self, parent: &Path, include: &str) -> PathBuf {
+        let path = self.resolver.resolve(parent, include);
+        let dependency = Dependency {
+            kind: DependencyKind::Include,
+            parent: normalize_path(parent),
+            path: normalize_path(&path),
+        };
+
+        let mut includes = self.includes.borrow_mut();
+        if !includes.contains(&dependency) {
+            includes.push(dependency);
+        }
+        path
+    }
+
+    fn read(&self, path: &Path) -> std::io::Result<Vec<u8>> {
+        self.resolver.read(path)
+    }
+}
+
+/// Find every file included by the shader source at the given path, directly or through
+/// other included files.
+pub fn include_dependencies(
+    path: impl AsRef<Path>,
+    resolver: &impl IncludeResolver,
+) -> Result<Vec<Dependency>, PreprocessError> {
+    let recorder = IncludeRecorder {
+        resolver,
+        includes: RefCell::new(Vec::new()),
+    };
+    read_source(path, &recorder)?;
+    Ok(recorder.includes.into_inner())
+}
+
+/// Parse the shader preset at the given path, along with the graph of every file it depends on.
+///
+/// In addition to the edges found when parsing the preset, the graph contains an
+/// [`Include`](DependencyKind::Include) edge for every file included by the shader sources
+/// of the preset.
+pub fn preset_dependencies(
+    path: impl AsRef<Path>,
+    context: WildcardContext,
+    fs: &impl PresetFileSystem,
+) -> Result<(ShaderPreset, DependencyGraph), BundleError> {
+    let (preset, mut graph) = ShaderPreset::try_parse_with_dependencies(path, context, fs)?;
+    for shader in &preset.shaders {
+        for include in include_dependencies(&shader.name, fs)? {
+            if !graph.dependencies.contains(&include) {
+                graph.dependencies.push(include);
+            }
+        }
+    }
+    Ok((preset, graph))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::dependency::preset_dependencies;
+    use librashader_presets::fs::MemoryFileSystem;
+    use librashader_presets::{Dependency, DependencyKind, WildcardContext};
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn finds_nested_includes() {
+        let mut fs = MemoryFileSystem::new();
+        fs.insert(
+            "shaders/first.slang",
+            "#version 450\n#include \"../include/common.inc\"\n",
+        );
+        fs.insert(
+            "shaders/second.slang",
+            "#version 450\n#include \"../include/common.inc\"\n",
+        );
+        fs.insert("include/common.inc", "#include \"nested/math.inc\"\n");
+        fs.insert("include/nested/math.inc", "#define PI 3.14159\n");
+        fs.insert(
+            "presets/chain.slangp",
+            "shaders = 2\nshader0 = ../shaders/first.slang\nshader1 = ../shaders/second.slang\n",
+        );
+
+        let (_, graph) =
+            preset_dependencies("presets/chain.slangp", WildcardContext::new(), &fs).unwrap();
+
+        let includes: Vec<&Dependency> = graph
+            .dependencies
+            .iter()
+            .filter(|d| d.kind == DependencyKind::Include)
+            .collect();
+        assert_eq!(
+            vec![
+                (
+                    Path::new("/shaders/first.slang"),
+                    Path::new("/include/common.inc")
+                ),
+                (
+                    Path::new("/include/common.inc"),
+                    Path::new("/include/nested/math.inc")
+                ),
+                (
+                    Path::new("/shaders/second.slang"),
+                    Path::new("/include/common.inc")
+                ),
+            ],
+            includes
+                .iter()
+                .map(|d| (d.parent.as_path(), d.path.as_path()))
+                .collect::<Vec<_>>()
+        );
+
+        let files: Vec<PathBuf> = graph.files().map(Path::to_path_buf).collect();
+        assert_eq!(5, files.len());
+        assert_eq!(
+            vec![0, 1],
+            graph.affected_passes(Path::new("/include/nested/math.inc"))
+        );
+    }
+}
@@ -1,4 +1,5 @@
 use librashader_common::map::ShortString;
+use librashader_presets::ParsePresetError;
 use std::convert::Infallible;
 use std::path::PathBuf;
 use thiserror::Error;
@@ -37,6 +38,20 @@ pub enum PreprocessError {
     InvalidStage,
 }
 
+/// Error type for collecting the files a shader preset depends on.
+#[derive(Error, Debug)]
+pub enum BundleError {
+    /// An error occurred when parsing the shader preset.
+    #[error("error parsing the shader preset")]
+    PresetError(#[from] ParsePresetError),
+    /// An error occurred when preprocessing a shader in the preset.
+    #[error("error preprocessing a shader in the preset")]
+    PreprocessError(#[from] PreprocessError),
+    /// An IO error occurred when reading a dependency or writing the bundle.
+    #[error("error reading or writing a dependency of the preset")]
+    IOError(PathBuf, std::io::Error),
+}
+
 impl From<Infallible> for PreprocessError {
     fn from(_: Infallible) -> Self {
         unreachable!()
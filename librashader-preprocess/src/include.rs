@@ -1,24 +1,52 @@
+use crate::source_map::MappedSource;
 use crate::{PreprocessError, SourceOutput};
 use encoding_rs::{DecoderResult, WINDOWS_1252};
-use std::fs::File;
-use std::io::Read;
-use std::path::Path;
+use librashader_presets::{BuiltinShader, PresetFileSystem, ShaderDefine};
+use std::path::{Path, PathBuf};
 use std::str::Lines;
 
 #[cfg(feature = "line_directives")]
 const GL_GOOGLE_CPP_STYLE_LINE_DIRECTIVE: &str =
     "#extension GL_GOOGLE_cpp_style_line_directive : require";
 
-fn read_file(path: impl AsRef<Path>) -> Result<String, PreprocessError> {
-    let path = path.as_ref();
-    let mut buf = Vec::new();
-    File::open(path)
-        .and_then(|mut f| {
-            f.read_to_end(&mut buf)?;
-            Ok(())
-        })
+/// Resolves and reads the files included by a shader source with `#include`.
+///
+/// Any [`PresetFileSystem`] is also an include resolver, so the same filesystem used to
+/// parse a shader preset can be used to load the shaders it references.
+pub trait IncludeResolver {
+    /// Resolve the path of the file included as `include` by the file at `parent`.
+    ///
+    /// By default, includes are resolved relative to the directory of the parent file.
+    fn resolve(&self, parent: &Path, include: &str) -> PathBuf {
+        parent
+            .parent()
+            .map_or_else(|| PathBuf::from(include), |dir| dir.join(include))
+    }
+
+    /// Read the entire contents of the file at the given path.
+    fn read(&self, path: &Path) -> std::io::Result<Vec<u8>>;
+}
+
+impl<T: PresetFileSystem + ?Sized> IncludeResolver for T {
+    fn read(&self, path: &Path) -> std::io::Result<Vec<u8>> {
+        PresetFileSystem::read(self, path)
+    }
+}
+
+pub(crate) fn read_file(
+    path: &Path,
+    resolver: &dyn IncludeResolver,
+) -> Result<String, PreprocessError> {
+    if let Some(builtin) = BuiltinShader::from_path(path) {
+        return Ok(builtin.source().to_string());
+    }
+    let buf = resolver
+        .read(path)
         .map_err(|e| PreprocessError::IOError(path.to_path_buf(), e))?;
+    decode_source(path, buf)
+}
 
+pub(crate) fn decode_source(path: &Path, buf: Vec<u8>) -> Result<String, PreprocessError> {
     match String::from_utf8(buf) {
         Ok(s) => Ok(s),
         Err(e) => {
@@ -42,19 +70,43 @@ fn read_file(path: impl AsRef<Path>) -> Result<String, PreprocessError> {
     }
 }
 
-pub fn read_source(path: impl AsRef<Path>) -> Result<String, PreprocessError> {
+pub fn read_source(
+    path: impl AsRef<Path>,
+    resolver: &dyn IncludeResolver,
+) -> Result<String, PreprocessError> {
+    read_source_mapped(path, resolver).map(|source| source.text)
+}
+
+/// Read the source at the given path, recording where each line of the output was read from.
+pub(crate) fn read_source_mapped(
+    path: impl AsRef<Path>,
+    resolver: &dyn IncludeResolver,
+) -> Result<MappedSource, PreprocessError> {
     let path = path.as_ref();
-    let source = read_file(path)?;
-    let mut output = String::new();
+    #[cfg(feature = "parse_legacy_glsl")]
+    if crate::legacy::is_legacy_source(path) {
+        return crate::legacy::read_legacy_source(path, resolver);
+    }
+    let source = read_file(path, resolver)?;
+    read_source_str_mapped(&source, path, resolver)
+}
+
+pub(crate) fn read_source_str_mapped(
+    source: &str,
+    path: impl AsRef<Path>,
+    resolver: &dyn IncludeResolver,
+) -> Result<MappedSource, PreprocessError> {
+    let path = path.as_ref();
+    let mut output = MappedSource::default();
 
-    let source = source.trim();
+    let (source, first_line) = trim_source(source);
     let mut lines = source.lines();
 
     if let Some(header) = lines.next() {
         if !header.starts_with("#version ") {
             return Err(PreprocessError::MissingVersionHeader);
         }
-        output.push_line(header);
+        output.push_source_line(header, path, first_line);
     } else {
         return Err(PreprocessError::UnexpectedEof);
     }
@@ -63,19 +115,47 @@ pub fn read_source(path: impl AsRef<Path>) -> Result<String, PreprocessError> {
     output.push_line(GL_GOOGLE_CPP_STYLE_LINE_DIRECTIVE);
 
     output.mark_line(2, path.file_name().and_then(|f| f.to_str()).unwrap_or(""));
-    preprocess(lines, path, &mut output)?;
+    preprocess(lines, first_line + 1, path, resolver, &mut output)?;
 
     Ok(output)
 }
 
-fn preprocess(
+/// Trim the whitespace around a source, returning the trimmed source and the line number
+/// its first line has in the untrimmed source.
+pub(crate) fn trim_source(source: &str) -> (&str, usize) {
+    let trimmed = source.trim_start();
+    let skipped = source[..source.len() - trimmed.len()].matches('\n').count();
+    (trimmed.trim_end(), skipped + 1)
+}
+
+/// Insert `#define` directives for the given definitions right after the `#version` header
+/// of a source read with [`read_source`].
+pub(crate) fn inject_defines(source: MappedSource, defines: &[ShaderDefine]) -> MappedSource {
+    if defines.is_empty() {
+        return source;
+    }
+    let MappedSource { text, mut map } = source;
+    let (header, rest) = text.split_once('\n').unwrap_or((&text, ""));
+    let mut output = String::with_capacity(text.len());
+    output.push_line(header);
+    for define in defines {
+        output.push_line(&format!("#define {} {}", define.name, define.value));
+    }
+    output.push_str(rest);
+    map.insert_generated(1, defines.len());
+    MappedSource { text: output, map }
+}
+
+/// Expand the includes in the given lines of the file at `path`, where the first line has
+/// the line number `first_line`.
+pub(crate) fn preprocess(
     lines: Lines,
-    file_name: impl AsRef<Path>,
-    output: &mut String,
+    first_line: usize,
+    path: &Path,
+    resolver: &dyn IncludeResolver,
+    output: &mut impl SourceOutput,
 ) -> Result<(), PreprocessError> {
-    let file_name = file_name.as_ref();
-    let include_path = file_name.parent().unwrap();
-    let file_name = file_name.file_name().and_then(|f| f.to_str()).unwrap_or("");
+    let file_name = path.file_name().and_then(|f| f.to_str()).unwrap_or("");
 
     for (line_no, line) in lines.enumerate() {
         if let Some(include_file) = line.strip_prefix("#include ") {
@@ -84,11 +164,9 @@ fn preprocess(
                 return Err(PreprocessError::UnexpectedEol(line_no));
             }
 
-            let mut include_path = include_path.to_path_buf();
-            include_path.push(include_file);
-
-            let source = read_file(&include_path)?;
-            let source = source.trim();
+            let include_path = resolver.resolve(path, include_file);
+            let source = read_file(&include_path, resolver)?;
+            let (source, include_first_line) = trim_source(&source);
             let lines = source.lines();
 
             let include_file = include_path
@@ -96,17 +174,17 @@ fn preprocess(
                 .and_then(|f| f.to_str())
                 .unwrap_or("");
             output.mark_line(1, include_file);
-            preprocess(lines, include_path, output)?;
+            preprocess(lines, include_first_line, &include_path, resolver, output)?;
             output.mark_line(line_no + 1, file_name);
             continue;
         }
         if line.starts_with("#endif") || line.starts_with("#pragma") {
-            output.push_line(line);
+            output.push_source_line(line, path, first_line + line_no);
             output.mark_line(line_no + 2, file_name);
             continue;
         }
 
-        output.push_line(line)
+        output.push_source_line(line, path, first_line + line_no)
     }
     Ok(())
 }
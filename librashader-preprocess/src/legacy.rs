@@ -0,0 +1,611 @@
+//! Conversion of RetroArch legacy GLSL shaders into slang shaders.
+//!
+//! Legacy shaders keep both stages in a single `.glsl` file, behind `#if defined(VERTEX)` and
+//! `#if defined(FRAGMENT)`, and declare loose uniforms, attributes and varyings that Vulkan
+//! GLSL does not allow. A legacy shader is converted by repeating it once for each stage, and
+//! rewriting its declarations:
+//!
+//! * Attributes are bound to the `Position` and `TexCoord` locations of a slang shader.
+//! * Varyings are given locations by name, so they match between stages.
+//! * Uniforms for known semantics, such as `MVPMatrix`, `InputSize` or `FrameCount`, and for
+//!   parameters, are moved into a uniform buffer with the names of the equivalent slang
+//!   semantics, and the legacy names are defined to read from it.
+//! * Samplers are bound, and `Texture`, `OrigTexture` and `PrevTexture` are renamed to
+//!   `Source`, `Original` and `OriginalHistory1`.
+//!
+//! Since textures are never padded to a power of two, `TextureSize` is the same as `InputSize`.
+//! Uniforms without a slang equivalent, such as the `PassPrev` textures, are not supported.
+use crate::include::{preprocess, read_file, trim_source};
+use crate::source_map::{MappedSource, SourceMap};
+use crate::{IncludeResolver, PreprocessError, SourceOutput};
+use std::path::Path;
+
+#[cfg(feature = "line_directives")]
+const GL_GOOGLE_CPP_STYLE_LINE_DIRECTIVE: &str =
+    "#extension GL_GOOGLE_cpp_style_line_directive : require";
+
+const PRECISION_QUALIFIERS: [&str; 4] = ["COMPAT_PRECISION", "lowp", "mediump", "highp"];
+
+/// Whether the shader at the given path is a legacy GLSL shader.
+pub(crate) fn is_legacy_source(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "glsl")
+}
+
+/// Read the legacy shader at the given path, resolving its includes, and convert it into
+/// the source of a slang shader.
+///
+/// Lines that are rewritten are mapped to the line they were rewritten from.
+pub(crate) fn read_legacy_source(
+    path: &Path,
+    resolver: &dyn IncludeResolver,
+) -> Result<MappedSource, PreprocessError> {
+    let source = read_file(path, resolver)?;
+    let mut expanded = MappedSource::default();
+    let (source, first_line) = trim_source(&source);
+    preprocess(source.lines(), first_line, path, resolver, &mut expanded)?;
+    Ok(convert_legacy_source(&expanded))
+}
+
+/// Output for the lines written in place of a single line of the expanded legacy shader,
+/// which are all mapped to that line.
+struct RewrittenLine<'a> {
+    out: &'a mut MappedSource,
+    map: &'a SourceMap,
+    index: usize,
+}
+
+impl SourceOutput for RewrittenLine<'_> {
+    fn push_line(&mut self, str: &str) {
+        self.out.text.push_line(str);
+        self.out.map.push_from(self.map, self.index);
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Stage {
+    Vertex,
+    Fragment,
+}
+
+/// A declaration of a single uniform, attribute or varying.
+struct Declaration<'a> {
+    qualifier: &'a str,
+    ty: &'a str,
+    name: &'a str,
+}
+
+impl<'a> Declaration<'a> {
+    fn parse(line: &'a str) -> Option<Declaration<'a>> {
+        let line = line.split("//").next()?.trim();
+        let line = line.strip_suffix(';')?;
+        let mut tokens = line
+            .split_whitespace()
+            .filter(|token| !PRECISION_QUALIFIERS.contains(token));
+        let qualifier = tokens.next()?;
+        let ty = tokens.next()?;
+        let name = tokens.next()?;
+        if tokens.next().is_some() {
+            return None;
+        }
+        if !matches!(
+            qualifier,
+            "uniform"
+                | "attribute"
+                | "COMPAT_ATTRIBUTE"
+                | "varying"
+                | "COMPAT_VARYING"
+                | "in"
+                | "out"
+        ) {
+            return None;
+        }
+        Some(Declaration {
+            qualifier,
+            ty,
+            name,
+        })
+    }
+
+    fn is_attribute(&self, stage: Stage) -> bool {
+        matches!(self.qualifier, "attribute" | "COMPAT_ATTRIBUTE")
+            || (stage == Stage::Vertex && self.qualifier == "in")
+    }
+}
+
+/// The slang semantic a legacy uniform maps to, and how to read the legacy value from it.
+struct SemanticUniform {
+    member: String,
+    ty: &'static str,
+    swizzle: &'static str,
+}
+
+/// Get the number of frames back a `Prev` prefixed name refers to, and the rest of the name.
+fn history_index(name: &str) -> Option<(usize, &str)> {
+    let rest = name.strip_prefix("Prev")?;
+    let digits = rest.chars().take_while(char::is_ascii_digit).count();
+    let index = if digits == 0 {
+        1
+    } else {
+        rest[..digits].parse::<usize>().ok()? + 1
+    };
+    Some((index, &rest[digits..]))
+}
+
+fn semantic_uniform(name: &str) -> Option<SemanticUniform> {
+    let uniform = |member: &str, ty, swizzle| SemanticUniform {
+        member: member.to_string(),
+        ty,
+        swizzle,
+    };
+    match name {
+        "MVPMatrix" => Some(uniform("MVP", "mat4", "")),
+        "FrameCount" => Some(uniform("FrameCount", "uint", "")),
+        "FrameDirection" => Some(uniform("FrameDirection", "int", "")),
+        "OutputSize" => Some(uniform("OutputSize", "vec4", ".xy")),
+        "InputSize" | "TextureSize" => Some(uniform("SourceSize", "vec4", ".xy")),
+        "OrigInputSize" | "OrigTextureSize" => Some(uniform("OriginalSize", "vec4", ".xy")),
+        _ => {
+            let (index, rest) = history_index(name)?;
+            matches!(rest, "InputSize" | "TextureSize").then(|| SemanticUniform {
+                member: format!("OriginalHistory{index}Size"),
+                ty: "vec4",
+                swizzle: ".xy",
+            })
+        }
+    }
+}
+
+fn semantic_texture(name: &str) -> String {
+    match name {
+        "Texture" => String::from("Source"),
+        "OrigTexture" => String::from("Original"),
+        _ => match history_index(name) {
+            Some((index, "Texture")) => format!("OriginalHistory{index}"),
+            _ => name.to_string(),
+        },
+    }
+}
+
+/// Replace every whole-word occurrence of `from` with `to`.
+fn replace_word(line: &str, from: &str, to: &str) -> String {
+    let is_ident = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    let mut out = String::with_capacity(line.len());
+    let mut rest = line;
+    while let Some(index) = rest.find(from) {
+        let before = rest[..index].chars().next_back();
+        let after = rest[index + from.len()..].chars().next();
+        out.push_str(&rest[..index]);
+        if before.is_some_and(is_ident) || after.is_some_and(is_ident) {
+            out.push_str(from);
+        } else {
+            out.push_str(to);
+        }
+        rest = &rest[index + from.len()..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// The declarations of a legacy shader that are shared between stages.
+#[derive(Default)]
+struct LegacyShader<'a> {
+    /// `#pragma` and `#extension` lines, that are moved to the top of the shader, with
+    /// their index in the expanded source.
+    header: Vec<(usize, &'a str)>,
+    /// The lines of the shader, without the header, with their index in the expanded source.
+    body: Vec<(usize, String)>,
+    /// The members of the uniform buffer.
+    members: Vec<(String, &'static str)>,
+    /// The definitions of legacy uniform names.
+    defines: Vec<String>,
+    /// The names of varyings, by location.
+    varyings: Vec<&'a str>,
+    /// The names of samplers, by binding minus one.
+    samplers: Vec<String>,
+    /// Whether the shader declares its own fragment output.
+    declares_output: bool,
+}
+
+impl<'a> LegacyShader<'a> {
+    fn new(source: &'a str) -> Self {
+        let mut shader = LegacyShader::default();
+        let parameters: Vec<&str> = source
+            .lines()
+            .filter_map(|line| line.strip_prefix("#pragma parameter "))
+            .filter_map(|line| line.split_whitespace().next())
+            .collect();
+
+        for (index, line) in source.lines().enumerate() {
+            let trimmed = line.trim_start();
+            if trimmed.starts_with("#version") {
+                continue;
+            }
+            if trimmed.starts_with("#pragma parameter") || trimmed.starts_with("#extension") {
+                shader.header.push((index, trimmed));
+                continue;
+            }
+
+            if let Some(declaration) = Declaration::parse(line) {
+                match declaration.qualifier {
+                    "uniform" if declaration.ty.starts_with("sampler") => {
+                        let name = semantic_texture(declaration.name);
+                        if !shader.samplers.contains(&name) {
+                            shader.samplers.push(name);
+                        }
+                    }
+                    "uniform" => shader.declare_uniform(&declaration, &parameters),
+                    "out" if declaration.name == "FragColor" => shader.declares_output = true,
+                    "attribute" | "COMPAT_ATTRIBUTE" => {}
+                    _ => {
+                        if !shader.varyings.contains(&declaration.name) {
+                            shader.varyings.push(declaration.name);
+                        }
+                    }
+                }
+            }
+
+            let line = replace_word(line, "gl_FragColor", "FragColor");
+            shader
+                .body
+                .push((index, replace_word(&line, "texture2D", "texture")));
+        }
+        shader
+    }
+
+    fn declare_uniform(&mut self, declaration: &Declaration, parameters: &[&str]) {
+        let (member, ty, swizzle) = if let Some(uniform) = semantic_uniform(declaration.name) {
+            (uniform.member, uniform.ty, uniform.swizzle)
+        } else if parameters.contains(&declaration.name) {
+            (declaration.name.to_string(), "float", "")
+        } else {
+            return;
+        };
+
+        let define = format!(
+            "#define {} {}(global.{member}{swizzle})",
+            declaration.name, declaration.ty
+        );
+        if !self.defines.contains(&define) {
+            self.defines.push(define);
+        }
+        if !self.members.iter().any(|(name, _)| *name == member) {
+            self.members.push((member, ty));
+        }
+    }
+
+    /// Rewrite a line of the shader for the given stage.
+    fn rewrite(&self, line: &str, stage: Stage, out: &mut impl SourceOutput) {
+        let Some(declaration) = Declaration::parse(line) else {
+            out.push_line(line);
+            return;
+        };
+        let Declaration { ty, name, .. } = declaration;
+
+        if declaration.qualifier == "uniform" {
+            if ty.starts_with("sampler") {
+                // samplers can only be used by the fragment stage.
+                if stage == Stage::Fragment {
+                    let semantic = semantic_texture(name);
+                    // NOPANIC: every sampler was collected.
+                    let binding = self.samplers.iter().position(|s| *s == semantic).unwrap() + 1;
+                    out.push_line(&format!(
+                        "layout(set = 0, binding = {binding}) uniform {ty} {semantic};"
+                    ));
+                    if semantic != name {
+                        out.push_line(&format!("#define {name} {semantic}"));
+                    }
+                }
+            } else if !self.defines.iter().any(|define| {
+                define
+                    .strip_prefix("#define ")
+                    .is_some_and(|define| define.split(' ').next() == Some(name))
+            }) {
+                // uniforms without an equivalent semantic are left unset.
+                out.push_line(&format!("const {ty} {name} = {ty}(0);"));
+            }
+            return;
+        }
+
+        if declaration.is_attribute(stage) {
+            if stage == Stage::Fragment {
+                out.push_line(line);
+                return;
+            }
+            match (name, ty) {
+                ("VertexCoord", _) => out.push_line("layout(location = 0) in vec4 VertexCoord;"),
+                ("TexCoord", "vec2") => out.push_line("layout(location = 1) in vec2 TexCoord;"),
+                ("TexCoord", _) => {
+                    out.push_line("layout(location = 1) in vec2 LEGACY_TexCoord;");
+                    out.push_line(&format!("#define TexCoord {ty}(LEGACY_TexCoord, 0.0, 1.0)"));
+                }
+                // every texture has the same coordinates.
+                (name, _) if name.ends_with("TexCoord") => {
+                    out.push_line(&format!("#define {name} TexCoord"))
+                }
+                ("COLOR", _) => out.push_line(&format!("#define COLOR {ty}(1.0)")),
+                (name, _) => out.push_line(&format!("#define {name} {ty}(0.0)")),
+            }
+            return;
+        }
+
+        if stage == Stage::Fragment && declaration.qualifier == "out" {
+            out.push_line(&format!("layout(location = 0) out {ty} {name};"));
+            return;
+        }
+
+        let direction = match stage {
+            Stage::Vertex => "out",
+            Stage::Fragment => "in",
+        };
+        // the fragment output is only a varying in the vertex stage, where it is never compiled.
+        let Some(location) = self.varyings.iter().position(|v| *v == name) else {
+            out.push_line(line);
+            return;
+        };
+        out.push_line(&format!(
+            "layout(location = {location}) {direction} {ty} {name};"
+        ));
+    }
+
+    fn write_stage(&self, stage: Stage, map: &SourceMap, out: &mut MappedSource) {
+        match stage {
+            Stage::Vertex => {
+                out.push_line("#pragma stage vertex");
+                out.push_line("#define VERTEX");
+            }
+            Stage::Fragment => {
+                out.push_line("#pragma stage fragment");
+                out.push_line("#define FRAGMENT");
+            }
+        }
+        out.push_line("#define PARAMETER_UNIFORM");
+
+        if !self.members.is_empty() {
+            out.push_line("layout(std140, set = 0, binding = 0) uniform UBO");
+            out.push_line("{");
+            for (member, ty) in &self.members {
+                out.push_line(&format!("    {ty} {member};"));
+            }
+            out.push_line("} global;");
+            for define in &self.defines {
+                out.push_line(define);
+            }
+        }
+
+        if stage == Stage::Fragment && !self.declares_output {
+            out.push_line("layout(location = 0) out vec4 FragColor;");
+        }
+
+        for (index, line) in &self.body {
+            let mut out = RewrittenLine {
+                out: &mut *out,
+                map,
+                index: *index,
+            };
+            self.rewrite(line, stage, &mut out);
+        }
+    }
+}
+
+/// Convert the source of a legacy shader, with includes already resolved, into the source
+/// of a slang shader.
+pub(crate) fn convert_legacy_source(source: &MappedSource) -> MappedSource {
+    let shader = LegacyShader::new(&source.text);
+    let map = &source.map;
+    let mut out = MappedSource {
+        text: String::new(),
+        map: map.split(),
+    };
+    out.push_line("#version 450");
+    #[cfg(feature = "line_directives")]
+    out.push_line(GL_GOOGLE_CPP_STYLE_LINE_DIRECTIVE);
+    for (index, line) in &shader.header {
+        out.text.push_line(line);
+        out.map.push_from(map, *index);
+    }
+    shader.write_stage(Stage::Vertex, map, &mut out);
+    shader.write_stage(Stage::Fragment, map, &mut out);
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use crate::ShaderSource;
+    use librashader_presets::fs::MemoryFileSystem;
+    use librashader_presets::{ShaderPreset, WildcardContext};
+    use std::path::Path;
+
+    const LEGACY: &str = r#"// Tints the image.
+#pragma parameter TINT "Tint Strength" 0.5 0.0 1.0 0.05
+
+#if defined(VERTEX)
+
+#if __VERSION__ >= 130
+#define COMPAT_VARYING out
+#define COMPAT_ATTRIBUTE in
+#else
+#define COMPAT_VARYING varying
+#define COMPAT_ATTRIBUTE attribute
+#endif
+
+#ifdef GL_ES
+#define COMPAT_PRECISION mediump
+#else
+#define COMPAT_PRECISION
+#endif
+
+COMPAT_ATTRIBUTE vec4 VertexCoord;
+COMPAT_ATTRIBUTE vec4 COLOR;
+COMPAT_ATTRIBUTE vec4 TexCoord;
+COMPAT_VARYING vec4 COL0;
+COMPAT_VARYING vec4 TEX0;
+
+uniform mat4 MVPMatrix;
+uniform COMPAT_PRECISION int FrameCount;
+uniform COMPAT_PRECISION vec2 OutputSize;
+uniform COMPAT_PRECISION vec2 TextureSize;
+uniform COMPAT_PRECISION vec2 InputSize;
+
+void main()
+{
+    gl_Position = MVPMatrix * VertexCoord;
+    COL0 = COLOR;
+    TEX0.xy = TexCoord.xy * 1.0001;
+}
+
+#elif defined(FRAGMENT)
+
+#if __VERSION__ >= 130
+#define COMPAT_VARYING in
+#define COMPAT_TEXTURE texture
+out vec4 FragColor;
+#else
+#define COMPAT_VARYING varying
+#define FragColor gl_FragColor
+#define COMPAT_TEXTURE texture2D
+#endif
+
+#ifdef GL_ES
+#define COMPAT_PRECISION mediump
+#else
+#define COMPAT_PRECISION
+#endif
+
+uniform COMPAT_PRECISION int FrameDirection;
+uniform COMPAT_PRECISION vec2 TextureSize;
+uniform COMPAT_PRECISION vec2 InputSize;
+uniform sampler2D Texture;
+uniform sampler2D PrevTexture;
+COMPAT_VARYING vec4 TEX0;
+
+#ifdef PARAMETER_UNIFORM
+uniform COMPAT_PRECISION float TINT;
+#else
+#define TINT 0.5
+#endif
+
+void main()
+{
+    vec2 texel = TEX0.xy * TextureSize / InputSize;
+    vec4 color = COMPAT_TEXTURE(Texture, texel);
+    FragColor = mix(color, COMPAT_TEXTURE(PrevTexture, TEX0.xy), TINT);
+}
+#endif
+"#;
+
+    #[test]
+    fn converts_legacy_shader() {
+        let mut fs = MemoryFileSystem::new();
+        fs.insert("/shaders/tint.glsl", LEGACY);
+        let source = ShaderSource::load_with_resolver("/shaders/tint.glsl", &fs).unwrap();
+
+        assert!(source.parameters.contains_key("TINT"));
+        for stage in [&source.vertex, &source.fragment] {
+            assert!(stage.starts_with("#version 450"));
+            assert!(stage.contains("#define MVPMatrix mat4(global.MVP)"));
+            assert!(stage.contains("#define InputSize vec2(global.SourceSize.xy)"));
+            assert!(stage.contains("#define TINT float(global.TINT)"));
+            assert!(!stage.contains("uniform mat4 MVPMatrix;"));
+        }
+
+        assert!(source.vertex.contains("#define VERTEX"));
+        assert!(source
+            .vertex
+            .contains("layout(location = 0) in vec4 VertexCoord;"));
+        assert!(source
+            .vertex
+            .contains("layout(location = 1) in vec2 LEGACY_TexCoord;"));
+        assert!(source
+            .vertex
+            .contains("layout(location = 1) out vec4 TEX0;"));
+        assert!(!source.vertex.contains("sampler2D"));
+
+        assert!(source.fragment.contains("#define FRAGMENT"));
+        assert!(source
+            .fragment
+            .contains("layout(location = 1) in vec4 TEX0;"));
+        assert!(source
+            .fragment
+            .contains("layout(location = 0) out vec4 FragColor;"));
+        assert!(source
+            .fragment
+            .contains("layout(set = 0, binding = 1) uniform sampler2D Source;"));
+        assert!(source
+            .fragment
+            .contains("#define PrevTexture OriginalHistory1"));
+    }
+
+    #[test]
+    fn maps_legacy_lines_to_files() {
+        let mut fs = MemoryFileSystem::new();
+        fs.insert(
+            "/shaders/tint.glsl",
+            LEGACY.replace("uniform sampler2D Texture;", "#include \"sampler.inc\""),
+        );
+        fs.insert("/shaders/sampler.inc", "\nuniform sampler2D Texture;\n");
+        let source = ShaderSource::load_with_resolver("/shaders/tint.glsl", &fs).unwrap();
+
+        let find = |text: &str, needle: &str| {
+            text.lines()
+                .position(|line| line.contains(needle))
+                .map(|index| index + 1)
+                .unwrap()
+        };
+        let line_of = |needle: &str| {
+            LEGACY
+                .lines()
+                .position(|line| line.contains(needle))
+                .map(|index| index + 1)
+                .unwrap()
+        };
+        let shader = Path::new("/shaders/tint.glsl");
+
+        let position = find(&source.vertex, "gl_Position = ");
+        assert_eq!(
+            Some((shader, line_of("gl_Position = "))),
+            source.vertex_map.lookup(position)
+        );
+        let varying = find(&source.vertex, "out vec4 TEX0;");
+        assert_eq!(
+            Some((shader, line_of("COMPAT_VARYING vec4 TEX0;"))),
+            source.vertex_map.lookup(varying)
+        );
+        assert_eq!(
+            None,
+            source.vertex_map.lookup(find(&source.vertex, "} global;"))
+        );
+
+        let sampler = find(&source.fragment, "uniform sampler2D Source;");
+        assert_eq!(
+            Some((Path::new("/shaders/sampler.inc"), 2)),
+            source.fragment_map.lookup(sampler)
+        );
+        assert_eq!(source.fragment.lines().count(), source.fragment_map.len());
+    }
+
+    #[test]
+    fn loads_glslp_preset() {
+        let mut fs = MemoryFileSystem::new();
+        fs.insert("/shaders/tint.glsl", LEGACY);
+        fs.insert(
+            "/presets/tint.glslp",
+            concat!(
+                "shaders = 1\n",
+                "shader0 = ../shaders/tint.glsl\n",
+                "filter_linear0 = false\n",
+                "feedback_pass = 0\n",
+                "parameters = TINT\n",
+                "TINT = 0.25\n",
+            ),
+        );
+
+        let preset =
+            ShaderPreset::try_parse_with_fs("/presets/tint.glslp", WildcardContext::new(), &fs)
+                .unwrap();
+        assert_eq!(0, preset.feedback_pass);
+        assert_eq!(0.25, preset.parameters[0].value);
+
+        let source = ShaderSource::load_with_resolver(&preset.shaders[0].name, &fs).unwrap();
+        assert!(source.parameters.contains_key("TINT"));
+    }
+}
@@ -7,20 +7,53 @@
 //! The resulting [`ShaderSource`]can then be passed into a
 //! reflection target for reflection and compilation into the target shader format.
 //!
+//! Includes are read from disk by default. An [`IncludeResolver`] can be provided to
+//! load shader sources from other locations, such as memory or an archive. Any
+//! [`PresetFileSystem`](librashader_presets::PresetFileSystem) is also an include resolver.
+//!
+//! With the `parse_legacy_glsl` feature, RetroArch legacy `.glsl` shaders are converted into
+//! slang shaders when they are loaded, so they can be used by `.glslp` presets.
+//!
 //! Re-exported as [`librashader::preprocess`](https://docs.rs/librashader/latest/librashader/preprocess/index.html).
+mod bundle;
+mod dependency;
 mod error;
 mod include;
+#[cfg(feature = "parse_legacy_glsl")]
+mod legacy;
+mod parameters;
 mod pragma;
+mod source_map;
 mod stage;
+mod validate;
 
-use crate::include::read_source;
+use crate::include::{inject_defines, read_source_mapped, read_source_str_mapped};
+use crate::source_map::MappedSource;
+pub use bundle::export_preset;
+pub use dependency::{include_dependencies, preset_dependencies};
 pub use error::*;
+pub use include::IncludeResolver;
+pub use parameters::{
+    preset_parameter_tree, preset_parameters, ParameterKind, ParameterSection, ParameterTree,
+    PresetParameter,
+};
+pub use pragma::{PragmaDiagnostic, PragmaDiagnosticKind};
+pub use source_map::SourceMap;
+pub use validate::validate_preset;
+
+#[cfg(feature = "archive")]
+pub use bundle::pack_preset;
 use librashader_common::map::{FastHashMap, ShortString};
 use librashader_common::ImageFormat;
+use librashader_presets::fs::DiskFileSystem;
+pub use librashader_presets::ShaderDefine;
 use std::path::Path;
 
 /// The source file for a single shader pass.
-#[derive(Debug, Clone, PartialEq)]
+///
+/// Sources are compared by their contents. The source maps are not compared, so the same
+/// shader loaded from different directories is equal.
+#[derive(Debug, Clone)]
 pub struct ShaderSource {
     /// The source contents for the vertex shader.
     pub vertex: String,
@@ -36,10 +69,30 @@ pub struct ShaderSource {
 
     /// The image format the shader expects.
     pub format: ImageFormat,
+
+    /// Where each line of the vertex shader was read from.
+    ///
+    /// Errors reported by shader compilers against a line of [`ShaderSource::vertex`] can be
+    /// traced back to the file and line the author wrote with [`SourceMap::lookup`].
+    pub vertex_map: SourceMap,
+
+    /// Where each line of the fragment shader was read from.
+    pub fragment_map: SourceMap,
+}
+
+impl PartialEq for ShaderSource {
+    fn eq(&self, other: &Self) -> bool {
+        self.vertex == other.vertex
+            && self.fragment == other.fragment
+            && self.name == other.name
+            && self.parameters == other.parameters
+            && self.format == other.format
+    }
 }
 
 /// A user tweakable parameter for the shader as declared in source.
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ShaderParameter {
     /// The name of the parameter.
     pub id: ShortString,
@@ -59,12 +112,68 @@ impl ShaderSource {
     /// Load the source file at the given path, resolving includes relative to the location of the
     /// source file.
     pub fn load(path: impl AsRef<Path>) -> Result<ShaderSource, PreprocessError> {
-        load_shader_source(path)
+        load_shader_source(path, &DiskFileSystem)
+    }
+
+    /// Load the source file at the given path, reading the file and resolving its includes
+    /// with the given resolver.
+    pub fn load_with_resolver(
+        path: impl AsRef<Path>,
+        resolver: &impl IncludeResolver,
+    ) -> Result<ShaderSource, PreprocessError> {
+        load_shader_source(path, resolver)
+    }
+
+    /// Load the source file at the given path with the given resolver, defining the given
+    /// preprocessor macros.
+    ///
+    /// Each definition is inserted as a `#define` directive right after the `#version` header,
+    /// before any includes, so it is visible to the entire shader. Definitions are part of the
+    /// resulting source, so sources loaded with different definitions are cached separately.
+    pub fn load_with_defines(
+        path: impl AsRef<Path>,
+        resolver: &impl IncludeResolver,
+        defines: &[ShaderDefine],
+    ) -> Result<ShaderSource, PreprocessError> {
+        let source = read_source_mapped(path, resolver)?;
+        process_shader_source(inject_defines(source, defines))
+    }
+
+    /// Load the source file at the given path with the given resolver, and report every
+    /// `#pragma parameter` line that had to be loaded leniently.
+    ///
+    /// Pragmas are loaded the same way as [`ShaderSource::load`], which sets the values of a
+    /// parameter to 0 if they can not be parsed, and the step to 0.02 if it is missing.
+    /// Each time this happens is reported with the file and line of the pragma, along with
+    /// parameters with an initial value outside of their range, or a step that is not positive.
+    pub fn load_with_diagnostics(
+        path: impl AsRef<Path>,
+        resolver: &impl IncludeResolver,
+    ) -> Result<(ShaderSource, Vec<PragmaDiagnostic>), PreprocessError> {
+        let source = read_source_mapped(path, resolver)?;
+        let diagnostics = pragma::parameter_diagnostics(&source)?;
+        Ok((process_shader_source(source)?, diagnostics))
+    }
+
+    /// Load a shader from source text, resolving includes with the given resolver.
+    ///
+    /// `path` is the path the source text is treated as having been read from, and is used
+    /// to resolve includes relative to it.
+    pub fn load_from_str(
+        source: &str,
+        path: impl AsRef<Path>,
+        resolver: &impl IncludeResolver,
+    ) -> Result<ShaderSource, PreprocessError> {
+        let source = read_source_str_mapped(source, path, resolver)?;
+        process_shader_source(source)
     }
 }
 
 pub(crate) trait SourceOutput {
     fn push_line(&mut self, str: &str);
+    fn push_source_line(&mut self, str: &str, _file: &Path, _line_no: usize) {
+        self.push_line(str)
+    }
     fn mark_line(&mut self, line_no: usize, comment: &str) {
         #[cfg(feature = "line_directives")]
         self.push_line(&format!("#line {line_no} \"{comment}\""))
@@ -78,10 +187,17 @@ impl SourceOutput for String {
     }
 }
 
-pub(crate) fn load_shader_source(path: impl AsRef<Path>) -> Result<ShaderSource, PreprocessError> {
-    let source = read_source(path)?;
-    let meta = pragma::parse_pragma_meta(&source)?;
-    let text = stage::process_stages(&source)?;
+pub(crate) fn load_shader_source(
+    path: impl AsRef<Path>,
+    resolver: &dyn IncludeResolver,
+) -> Result<ShaderSource, PreprocessError> {
+    let source = read_source_mapped(path, resolver)?;
+    process_shader_source(source)
+}
+
+fn process_shader_source(source: MappedSource) -> Result<ShaderSource, PreprocessError> {
+    let meta = pragma::parse_pragma_meta(&source.text)?;
+    let text = stage::process_stages(&source.text, &source.map)?;
     let parameters = FastHashMap::from_iter(meta.parameters.into_iter().map(|p| (p.id.clone(), p)));
 
     Ok(ShaderSource {
@@ -90,18 +206,23 @@ pub(crate) fn load_shader_source(path: impl AsRef<Path>) -> Result<ShaderSource,
         name: meta.name,
         parameters,
         format: meta.format,
+        vertex_map: text.vertex_map,
+        fragment_map: text.fragment_map,
     })
 }
 
 #[cfg(test)]
 mod test {
     use crate::include::read_source;
-    use crate::{load_shader_source, pragma};
+    use crate::{load_shader_source, pragma, PreprocessError, ShaderDefine, ShaderSource};
+    use librashader_presets::fs::{DiskFileSystem, MemoryFileSystem};
+    use librashader_presets::{BuiltinShader, ShaderPreset, WildcardContext};
 
     #[test]
     pub fn load_file() {
         let result = load_shader_source(
             "../test/slang-shaders/blurs/shaders/royale/blur3x3-last-pass.slang",
+            &DiskFileSystem,
         )
         .unwrap();
         eprintln!("{:#}", result.vertex)
@@ -109,9 +230,11 @@ mod test {
 
     #[test]
     pub fn preprocess_file() {
-        let result =
-            read_source("../test/slang-shaders/blurs/shaders/royale/blur3x3-last-pass.slang")
-                .unwrap();
+        let result = read_source(
+            "../test/slang-shaders/blurs/shaders/royale/blur3x3-last-pass.slang",
+            &DiskFileSystem,
+        )
+        .unwrap();
         eprintln!("{result}")
     }
 
@@ -119,10 +242,101 @@ mod test {
     pub fn get_param_pragmas() {
         let result = read_source(
             "../test/slang-shaders/crt/shaders/crt-maximus-royale/src/ntsc_pass1.slang",
+            &DiskFileSystem,
         )
         .unwrap();
 
         let params = pragma::parse_pragma_meta(result).unwrap();
         eprintln!("{params:?}")
     }
+
+    const STOCK: &str = r#"#version 450
+#include "../include/params.inc"
+
+#pragma stage vertex
+layout(location = 0) in vec4 Position;
+void main() { gl_Position = Position; }
+
+#pragma stage fragment
+layout(location = 0) out vec4 FragColor;
+void main() { FragColor = vec4(STRENGTH); }
+"#;
+
+    const PARAMS: &str = r#"#pragma parameter STRENGTH "Strength" 0.5 0.0 1.0 0.1
+"#;
+
+    #[test]
+    pub fn load_from_memory() {
+        let mut fs = MemoryFileSystem::new();
+        fs.insert("/shaders/stock.slang", STOCK);
+        fs.insert("/include/params.inc", PARAMS);
+
+        let source = ShaderSource::load_with_resolver("/shaders/stock.slang", &fs).unwrap();
+        assert!(source.parameters.contains_key("STRENGTH"));
+        assert!(source.fragment.contains("FragColor = vec4(STRENGTH);"));
+
+        let from_str = ShaderSource::load_from_str(STOCK, "/shaders/stock.slang", &fs).unwrap();
+        assert_eq!(source, from_str);
+
+        fs.remove("/include/params.inc");
+        let err = ShaderSource::load_with_resolver("/shaders/stock.slang", &fs).unwrap_err();
+        assert!(matches!(err, PreprocessError::IOError(path, _)
+            if path.ends_with("include/params.inc")));
+    }
+
+    #[test]
+    pub fn load_preset_passes_from_memory() {
+        let mut fs = MemoryFileSystem::new();
+        fs.insert("/shaders/stock.slang", STOCK);
+        fs.insert("/include/params.inc", PARAMS);
+        fs.insert(
+            "/presets/stock.slangp",
+            "shaders = 1\nshader0 = ../shaders/stock.slang\n",
+        );
+
+        let preset =
+            ShaderPreset::try_parse_with_fs("/presets/stock.slangp", WildcardContext::new(), &fs)
+                .unwrap();
+        let source = ShaderSource::load_with_resolver(&preset.shaders[0].name, &fs).unwrap();
+        assert!(source.parameters.contains_key("STRENGTH"));
+    }
+
+    #[test]
+    pub fn load_with_defines() {
+        let mut fs = MemoryFileSystem::new();
+        fs.insert("/shaders/stock.slang", STOCK);
+        fs.insert("/include/params.inc", PARAMS);
+
+        let defines = [
+            ShaderDefine::new("USE_FAST_PATH", "1"),
+            ShaderDefine::new("LIBRASHADER_TARGET_WGSL", "1"),
+        ];
+        let source =
+            ShaderSource::load_with_defines("/shaders/stock.slang", &fs, &defines).unwrap();
+        for stage in [&source.vertex, &source.fragment] {
+            let mut lines = stage.lines();
+            assert_eq!(Some("#version 450"), lines.next());
+            assert_eq!(Some("#define USE_FAST_PATH 1"), lines.next());
+            assert_eq!(Some("#define LIBRASHADER_TARGET_WGSL 1"), lines.next());
+        }
+        assert!(source.parameters.contains_key("STRENGTH"));
+
+        let plain = ShaderSource::load_with_defines("/shaders/stock.slang", &fs, &[]).unwrap();
+        assert_eq!(
+            ShaderSource::load_with_resolver("/shaders/stock.slang", &fs).unwrap(),
+            plain
+        );
+        assert_ne!(source.fragment, plain.fragment);
+    }
+
+    #[test]
+    pub fn load_builtin_shaders() {
+        for shader in BuiltinShader::ALL {
+            let source = ShaderSource::load(shader.path()).unwrap();
+            assert!(source.fragment.contains("FragColor"));
+        }
+
+        let sharp = ShaderSource::load(BuiltinShader::SharpBilinear.path()).unwrap();
+        assert!(sharp.parameters.contains_key("SHARP_BILINEAR_PRE_SCALE"));
+    }
 }
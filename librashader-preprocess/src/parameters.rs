@@ -0,0 +1,322 @@
+use crate::include::{read_source, IncludeResolver};
+use crate::{pragma, PreprocessError, ShaderParameter};
+use librashader_presets::ShaderPreset;
+
+/// A shader parameter declared by one or more passes of a shader preset.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PresetParameter {
+    /// The parameter, as declared by the first pass that declares it.
+    pub parameter: ShaderParameter,
+    /// The value of the parameter, as set by the preset, or the initial value if it is not set.
+    pub value: f32,
+    /// The index of every pass that declares the parameter, in order.
+    pub passes: Vec<i32>,
+    /// Declarations by later passes that differ from the first declaration, with the index of
+    /// the pass that declares them.
+    pub conflicts: Vec<(i32, ShaderParameter)>,
+}
+
+impl PresetParameter {
+    /// Whether any pass declares the parameter differently from the first pass that declares it.
+    pub fn is_conflicting(&self) -> bool {
+        !self.conflicts.is_empty()
+    }
+}
+
+/// Get every parameter declared by the passes of a shader preset, in the order they are
+/// declared across the filter chain.
+///
+/// Each parameter appears once, no matter how many passes declare it. Passes are read in
+/// order, and parameters are ordered by the first pass that declares them, then by the order
+/// they are declared in that pass. Shader sources are read with the given resolver, but are
+/// not compiled.
+pub fn preset_parameters(
+    preset: &ShaderPreset,
+    resolver: &impl IncludeResolver,
+) -> Result<Vec<PresetParameter>, PreprocessError> {
+    let mut parameters: Vec<PresetParameter> = Vec::new();
+    for shader in &preset.shaders {
+        let source = read_source(&shader.name, resolver)?;
+        let meta = pragma::parse_pragma_meta(source)?;
+        for declared in meta.parameters {
+            let Some(existing) = parameters
+                .iter_mut()
+                .find(|existing| existing.parameter.id == declared.id)
+            else {
+                let value = preset
+                    .parameters
+                    .iter()
+                    .find(|parameter| parameter.name == declared.id)
+                    .map_or(declared.initial, |parameter| parameter.value);
+                parameters.push(PresetParameter {
+                    parameter: declared,
+                    value,
+                    passes: vec![shader.id],
+                    conflicts: Vec::new(),
+                });
+                continue;
+            };
+
+            if !existing.passes.contains(&shader.id) {
+                existing.passes.push(shader.id);
+            }
+            if existing.parameter != declared {
+                existing.conflicts.push((shader.id, declared));
+            }
+        }
+    }
+    Ok(parameters)
+}
+
+/// How a shader parameter is meant to be shown.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ParameterKind {
+    /// A parameter with a value that can be changed.
+    Value,
+    /// A parameter that can not be changed, used as the title of the parameters after it,
+    /// such as `[ SECTION ]`.
+    Header,
+    /// A parameter that can not be changed, and has no title, used to separate parameters.
+    Separator,
+}
+
+impl ShaderParameter {
+    /// Get how the parameter is meant to be shown.
+    ///
+    /// Parameters with an empty range, such as `0.0 0.0 0.0`, can never be changed, and are
+    /// used by presets as headers and separators. They are headers if their description has
+    /// any letters or digits, and separators otherwise.
+    pub fn kind(&self) -> ParameterKind {
+        if self.minimum != self.maximum {
+            ParameterKind::Value
+        } else if self.description.chars().any(char::is_alphanumeric) {
+            ParameterKind::Header
+        } else {
+            ParameterKind::Separator
+        }
+    }
+
+    /// Get the title of a header parameter, without the decoration around it.
+    ///
+    /// For example, the title of `"[ CRT SETTINGS ]:"` is `"CRT SETTINGS"`.
+    pub fn title(&self) -> &str {
+        self.description
+            .trim_matches(|c: char| !c.is_alphanumeric() && !matches!(c, '(' | ')' | '\'' | '"'))
+    }
+}
+
+/// A group of parameters under a header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ParameterSection {
+    /// The index of the header parameter of the section in [`ParameterTree::parameters`].
+    ///
+    /// Parameters before the first header, or after a separator, are in a section with
+    /// no header.
+    pub header: Option<usize>,
+    /// The indices of the parameters in the section in [`ParameterTree::parameters`],
+    /// in order.
+    pub parameters: Vec<usize>,
+}
+
+/// The parameters of a shader preset, grouped into sections by their headers.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ParameterTree {
+    /// Every parameter, including headers and separators, in declaration order.
+    pub parameters: Vec<PresetParameter>,
+    /// The sections of parameters, in order. Sections never contain headers or separators,
+    /// and sections without a header are never empty.
+    pub sections: Vec<ParameterSection>,
+}
+
+impl ParameterTree {
+    /// Group a list of parameters into sections.
+    ///
+    /// A header starts a new section, and a separator ends the current section. Parameters
+    /// after a separator are in a section with no header, until the next header.
+    pub fn new(parameters: Vec<PresetParameter>) -> ParameterTree {
+        let mut sections: Vec<ParameterSection> = Vec::new();
+        let mut current = ParameterSection {
+            header: None,
+            parameters: Vec::new(),
+        };
+
+        for (index, parameter) in parameters.iter().enumerate() {
+            let next = match parameter.parameter.kind() {
+                ParameterKind::Value => {
+                    current.parameters.push(index);
+                    continue;
+                }
+                ParameterKind::Header => Some(index),
+                ParameterKind::Separator => None,
+            };
+
+            let section = std::mem::replace(
+                &mut current,
+                ParameterSection {
+                    header: next,
+                    parameters: Vec::new(),
+                },
+            );
+            if section.header.is_some() || !section.parameters.is_empty() {
+                sections.push(section);
+            }
+        }
+        if current.header.is_some() || !current.parameters.is_empty() {
+            sections.push(current);
+        }
+
+        ParameterTree {
+            parameters,
+            sections,
+        }
+    }
+
+    /// Get the header parameter of a section.
+    pub fn header(&self, section: &ParameterSection) -> Option<&PresetParameter> {
+        section.header.map(|index| &self.parameters[index])
+    }
+
+    /// Iterate over the parameters in a section.
+    pub fn section_parameters<'a>(
+        &'a self,
+        section: &'a ParameterSection,
+    ) -> impl Iterator<Item = &'a PresetParameter> {
+        section
+            .parameters
+            .iter()
+            .map(|&index| &self.parameters[index])
+    }
+}
+
+/// Get every parameter declared by the passes of a shader preset, grouped into sections
+/// by their headers.
+///
+/// See [`preset_parameters`] for how parameters are ordered, and [`ParameterTree::new`] for
+/// how they are grouped.
+pub fn preset_parameter_tree(
+    preset: &ShaderPreset,
+    resolver: &impl IncludeResolver,
+) -> Result<ParameterTree, PreprocessError> {
+    preset_parameters(preset, resolver).map(ParameterTree::new)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{preset_parameter_tree, preset_parameters, ParameterKind};
+    use librashader_presets::fs::MemoryFileSystem;
+    use librashader_presets::{ShaderPreset, WildcardContext};
+
+    #[test]
+    fn orders_and_deduplicates_parameters() {
+        let mut fs = MemoryFileSystem::new();
+        fs.insert(
+            "/shaders/first.slang",
+            concat!(
+                "#version 450\n",
+                "#pragma parameter ZOOM \"Zoom\" 1.0 0.5 2.0 0.1\n",
+                "#pragma parameter BRIGHTNESS \"Brightness\" 1.0 0.0 2.0 0.05\n",
+            ),
+        );
+        fs.insert(
+            "/shaders/second.slang",
+            concat!(
+                "#version 450\n",
+                "#pragma parameter ALPHA \"Alpha\" 1.0 0.0 1.0 0.1\n",
+                "#pragma parameter BRIGHTNESS \"Brightness\" 1.0 0.0 4.0 0.05\n",
+                "#pragma parameter ZOOM \"Zoom\" 1.0 0.5 2.0 0.1\n",
+            ),
+        );
+        fs.insert(
+            "/presets/chain.slangp",
+            concat!(
+                "shaders = 2\n",
+                "shader0 = ../shaders/first.slang\n",
+                "shader1 = ../shaders/second.slang\n",
+                "parameters = ALPHA\n",
+                "ALPHA = 0.5\n",
+            ),
+        );
+
+        let preset =
+            ShaderPreset::try_parse_with_fs("/presets/chain.slangp", WildcardContext::new(), &fs)
+                .unwrap();
+        let parameters = preset_parameters(&preset, &fs).unwrap();
+
+        let ids: Vec<&str> = parameters.iter().map(|p| p.parameter.id.as_str()).collect();
+        assert_eq!(vec!["ZOOM", "BRIGHTNESS", "ALPHA"], ids);
+
+        assert_eq!(vec![0, 1], parameters[0].passes);
+        assert!(!parameters[0].is_conflicting());
+
+        assert_eq!(vec![0, 1], parameters[1].passes);
+        assert_eq!(2.0, parameters[1].parameter.maximum);
+        assert_eq!(1, parameters[1].conflicts.len());
+        assert_eq!(1, parameters[1].conflicts[0].0);
+        assert_eq!(4.0, parameters[1].conflicts[0].1.maximum);
+
+        assert_eq!(vec![1], parameters[2].passes);
+        assert_eq!(0.5, parameters[2].value);
+        assert_eq!(1.0, parameters[0].value);
+    }
+
+    #[test]
+    fn groups_parameters_into_sections() {
+        let mut fs = MemoryFileSystem::new();
+        fs.insert(
+            "/shaders/sections.slang",
+            concat!(
+                "#version 450\n",
+                "#pragma parameter ZOOM \"Zoom\" 1.0 0.5 2.0 0.1\n",
+                "#pragma parameter HEADER_CRT \"[ CRT SETTINGS ]:\" 0 0 0 0\n",
+                "#pragma parameter SCANLINES \"Scanlines\" 1.0 0.0 1.0 1.0\n",
+                "#pragma parameter MASK \"Mask\" 0.0 0.0 3.0 1.0\n",
+                "#pragma parameter SPACE \" \" 0 0 0 0\n",
+                "#pragma parameter GAMMA \"Gamma\" 2.2 1.0 3.0 0.1\n",
+                "#pragma parameter HEADER_EMPTY \"=== EMPTY ===\" 0 0 0 0\n",
+            ),
+        );
+        fs.insert(
+            "/presets/sections.slangp",
+            "shaders = 1\nshader0 = ../shaders/sections.slang\n",
+        );
+
+        let preset = ShaderPreset::try_parse_with_fs(
+            "/presets/sections.slangp",
+            WildcardContext::new(),
+            &fs,
+        )
+        .unwrap();
+        let tree = preset_parameter_tree(&preset, &fs).unwrap();
+        assert_eq!(7, tree.parameters.len());
+
+        let sections: Vec<(Option<&str>, Vec<&str>)> = tree
+            .sections
+            .iter()
+            .map(|section| {
+                (
+                    tree.header(section).map(|header| header.parameter.title()),
+                    tree.section_parameters(section)
+                        .map(|p| p.parameter.id.as_str())
+                        .collect(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            vec![
+                (None, vec!["ZOOM"]),
+                (Some("CRT SETTINGS"), vec!["SCANLINES", "MASK"]),
+                (None, vec!["GAMMA"]),
+                (Some("EMPTY"), vec![]),
+            ],
+            sections
+        );
+        assert_eq!(
+            ParameterKind::Separator,
+            tree.parameters[4].parameter.kind()
+        );
+    }
+}
@@ -1,3 +1,4 @@
+use crate::source_map::MappedSource;
 use crate::{PreprocessError, ShaderParameter};
 use librashader_common::ImageFormat;
 use nom::bytes::complete::{is_not, tag, take_while};
@@ -8,6 +9,8 @@ use nom::combinator::opt;
 use nom::number::complete::float;
 use nom::sequence::delimited;
 use nom::IResult;
+use std::fmt::{Display, Formatter};
+use std::path::PathBuf;
 use std::str::FromStr;
 
 #[derive(Debug)]
@@ -17,7 +20,81 @@ pub(crate) struct ShaderMeta {
     pub(crate) name: Option<ShortString>,
 }
 
+/// A `#pragma parameter` line that was accepted, but had to be loaded leniently.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PragmaDiagnostic {
+    /// The file the pragma was declared in.
+    pub file: PathBuf,
+    /// The line number of the pragma in the file, starting from 1.
+    pub line: usize,
+    /// The name of the parameter.
+    pub parameter: ShortString,
+    /// What was wrong with the pragma.
+    pub kind: PragmaDiagnosticKind,
+}
+
+/// What was wrong with a leniently loaded `#pragma parameter` line.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PragmaDiagnosticKind {
+    /// The initial value, minimum and maximum could not be parsed, so they were all set to 0.
+    MalformedValues(String),
+    /// The step was missing, so it was set to 0.02.
+    MissingStep,
+    /// The initial value was not between the minimum and the maximum.
+    InitialOutOfRange {
+        /// The initial value of the parameter.
+        initial: f32,
+        /// The minimum value of the parameter.
+        minimum: f32,
+        /// The maximum value of the parameter.
+        maximum: f32,
+    },
+    /// The step was zero or negative, so the parameter can not be changed by stepping.
+    InvalidStep(f32),
+}
+
+impl Display for PragmaDiagnosticKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PragmaDiagnosticKind::MalformedValues(values) => {
+                write!(f, "could not parse the values `{values}`, defaulting to 0")
+            }
+            PragmaDiagnosticKind::MissingStep => write!(f, "missing step, defaulting to 0.02"),
+            PragmaDiagnosticKind::InitialOutOfRange {
+                initial,
+                minimum,
+                maximum,
+            } => write!(
+                f,
+                "initial value {initial} is outside of the range [{minimum}, {maximum}]"
+            ),
+            PragmaDiagnosticKind::InvalidStep(step) => {
+                write!(f, "step {step} is not positive")
+            }
+        }
+    }
+}
+
+impl Display for PragmaDiagnostic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}:{}: parameter {}: {}",
+            self.file.display(),
+            self.line,
+            self.parameter,
+            self.kind
+        )
+    }
+}
+
 fn parse_parameter_string(input: &str) -> Result<ShaderParameter, PreprocessError> {
+    parse_parameter_string_with_diagnostics(input).map(|(parameter, _)| parameter)
+}
+
+fn parse_parameter_string_with_diagnostics(
+    input: &str,
+) -> Result<(ShaderParameter, Vec<PragmaDiagnosticKind>), PreprocessError> {
     fn parse_parameter_string_name(input: &str) -> IResult<&str, (&str, &str)> {
         let (input, _) = tag("#pragma parameter ")(input)?;
         let (input, name) = take_while(|c| c != ' ' && c != '\t')(input)?;
@@ -31,7 +108,7 @@ fn parse_parameter_string(input: &str) -> Result<ShaderParameter, PreprocessErro
         name: &'a str,
         description: &'a str,
         input: &'b str,
-    ) -> IResult<&'b str, ShaderParameter> {
+    ) -> IResult<&'b str, (ShaderParameter, bool)> {
         let (input, initial) = float(input)?;
         let (input, _) = multispace1(input)?;
         let (input, minimum) = float(input)?;
@@ -49,14 +126,17 @@ fn parse_parameter_string(input: &str) -> Result<ShaderParameter, PreprocessErro
         let (input, step) = opt(float)(input)?;
         Ok((
             input,
-            ShaderParameter {
-                id: name.into(),
-                description: description.to_string(),
-                initial,
-                minimum,
-                maximum,
-                step: step.unwrap_or(0.02),
-            },
+            (
+                ShaderParameter {
+                    id: name.into(),
+                    description: description.to_string(),
+                    initial,
+                    minimum,
+                    maximum,
+                    step: step.unwrap_or(0.02),
+                },
+                step.is_none(),
+            ),
         ))
     }
 
@@ -66,18 +146,65 @@ fn parse_parameter_string(input: &str) -> Result<ShaderParameter, PreprocessErro
 
     // some shaders do some really funky things with their pragmas so we need to be lenient and ignore
     // that it can be set at all.
-    if let Ok((_, param)) = parse_parameter_string_inner(name, description, params) {
-        Ok(param)
-    } else {
-        Ok(ShaderParameter {
+    let Ok((_, (param, missing_step))) = parse_parameter_string_inner(name, description, params)
+    else {
+        let param = ShaderParameter {
             id: name.into(),
             description: description.to_string(),
             initial: 0f32,
             minimum: 0f32,
             maximum: 0f32,
             step: 0f32,
-        })
+        };
+        return Ok((
+            param,
+            vec![PragmaDiagnosticKind::MalformedValues(
+                params.trim().to_string(),
+            )],
+        ));
+    };
+
+    let mut diagnostics = Vec::new();
+    if missing_step {
+        diagnostics.push(PragmaDiagnosticKind::MissingStep);
+    }
+    if !(param.minimum..=param.maximum).contains(&param.initial) {
+        diagnostics.push(PragmaDiagnosticKind::InitialOutOfRange {
+            initial: param.initial,
+            minimum: param.minimum,
+            maximum: param.maximum,
+        });
     }
+    // headers and separators have an empty range, and are not meant to be stepped.
+    if param.step <= 0.0 && param.minimum != param.maximum {
+        diagnostics.push(PragmaDiagnosticKind::InvalidStep(param.step));
+    }
+    Ok((param, diagnostics))
+}
+
+/// Find every `#pragma parameter` line in a source that had to be loaded leniently, reported
+/// against the file and line the source map traces the pragma back to.
+pub(crate) fn parameter_diagnostics(
+    source: &MappedSource,
+) -> Result<Vec<PragmaDiagnostic>, PreprocessError> {
+    let mut diagnostics = Vec::new();
+    for (index, line) in source.text.lines().enumerate() {
+        if !line.starts_with("#pragma parameter ") {
+            continue;
+        }
+        // pragmas are only ever read from files, so they are always mapped.
+        let Some((file, line_no)) = source.map.lookup(index + 1) else {
+            continue;
+        };
+        let (parameter, kinds) = parse_parameter_string_with_diagnostics(line)?;
+        diagnostics.extend(kinds.into_iter().map(|kind| PragmaDiagnostic {
+            file: file.to_path_buf(),
+            line: line_no,
+            parameter: parameter.id.clone(),
+            kind,
+        }));
+    }
+    Ok(diagnostics)
 }
 
 pub(crate) fn parse_pragma_meta(source: impl AsRef<str>) -> Result<ShaderMeta, PreprocessError> {
@@ -128,8 +255,10 @@ pub(crate) fn parse_pragma_meta(source: impl AsRef<str>) -> Result<ShaderMeta, P
 
 #[cfg(test)]
 mod test {
-    use crate::pragma::parse_parameter_string;
-    use crate::ShaderParameter;
+    use crate::pragma::{parse_parameter_string, PragmaDiagnosticKind};
+    use crate::{ShaderParameter, ShaderSource};
+    use librashader_presets::fs::MemoryFileSystem;
+    use std::path::Path;
 
     #[test]
     fn parses_parameter_pragma() {
@@ -172,4 +301,70 @@ mod test {
             .unwrap()
         )
     }
+
+    #[test]
+    fn reports_lenient_parameter_pragmas() {
+        let mut fs = MemoryFileSystem::new();
+        fs.insert(
+            "/shaders/lint.slang",
+            concat!(
+                "#version 450\n",
+                "#include \"params.inc\"\n",
+                "#pragma parameter GOOD \"Good\" 0.5 0.0 1.0 0.1\n",
+                "#pragma parameter HEADER \"[ HEADER ]\" 0 0 0 0\n",
+                "#pragma parameter OUTSIDE \"Outside\" 2.0 0.0 1.0 0.1\n",
+            ),
+        );
+        fs.insert(
+            "/shaders/params.inc",
+            concat!(
+                "\n",
+                "#pragma parameter NO_STEP \"No Step\" 2.2 1.8 2.4\n",
+                "#pragma parameter FUNKY \"Funky\" 1.0 0.0 MAX 0.1\n",
+                "#pragma parameter STUCK \"Stuck\" 0.5 0.0 1.0 -0.1\n",
+            ),
+        );
+
+        let (source, diagnostics) =
+            ShaderSource::load_with_diagnostics("/shaders/lint.slang", &fs).unwrap();
+        assert_eq!(0.0, source.parameters["FUNKY"].maximum);
+
+        let found: Vec<(&Path, usize, &str, &PragmaDiagnosticKind)> = diagnostics
+            .iter()
+            .map(|d| (d.file.as_path(), d.line, d.parameter.as_str(), &d.kind))
+            .collect();
+        assert_eq!(
+            vec![
+                (
+                    Path::new("/shaders/params.inc"),
+                    2,
+                    "NO_STEP",
+                    &PragmaDiagnosticKind::MissingStep
+                ),
+                (
+                    Path::new("/shaders/params.inc"),
+                    3,
+                    "FUNKY",
+                    &PragmaDiagnosticKind::MalformedValues("1.0 0.0 MAX 0.1".into())
+                ),
+                (
+                    Path::new("/shaders/params.inc"),
+                    4,
+                    "STUCK",
+                    &PragmaDiagnosticKind::InvalidStep(-0.1)
+                ),
+                (
+                    Path::new("/shaders/lint.slang"),
+                    5,
+                    "OUTSIDE",
+                    &PragmaDiagnosticKind::InitialOutOfRange {
+                        initial: 2.0,
+                        minimum: 0.0,
+                        maximum: 1.0
+                    }
+                ),
+            ],
+            found
+        );
+    }
 }
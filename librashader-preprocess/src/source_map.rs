@@ -0,0 +1,152 @@
+use crate::SourceOutput;
+use std::path::{Path, PathBuf};
+
+/// Maps the lines of a preprocessed shader stage back to the files and lines they were read from.
+///
+/// Lines generated by the preprocessor, such as `#line` directives and injected definitions,
+/// do not map to any file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SourceMap {
+    files: Vec<PathBuf>,
+    lines: Vec<Option<(usize, usize)>>,
+}
+
+impl SourceMap {
+    /// Get the file and line number that the given line of the output was read from.
+    ///
+    /// Line numbers start from 1, as reported by shader compilers.
+    pub fn lookup(&self, line: usize) -> Option<(&Path, usize)> {
+        let (file, line) = self.location(line.checked_sub(1)?)?;
+        Some((&self.files[file], line))
+    }
+
+    /// Every file that lines of the output were read from.
+    pub fn files(&self) -> &[PathBuf] {
+        &self.files
+    }
+
+    /// The number of lines in the output.
+    pub fn len(&self) -> usize {
+        self.lines.len()
+    }
+
+    /// Whether the output has no lines.
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+
+    fn location(&self, index: usize) -> Option<(usize, usize)> {
+        self.lines.get(index).copied().flatten()
+    }
+
+    fn push(&mut self, file: &Path, line: usize) {
+        let file = match self.files.iter().rposition(|existing| existing == file) {
+            Some(file) => file,
+            None => {
+                self.files.push(file.to_path_buf());
+                self.files.len() - 1
+            }
+        };
+        self.lines.push(Some((file, line)));
+    }
+
+    /// Create an empty map that can take lines from this map.
+    pub(crate) fn split(&self) -> SourceMap {
+        SourceMap {
+            files: self.files.clone(),
+            lines: Vec::new(),
+        }
+    }
+
+    /// Add the line at `index` in `parent` to the end of this map, which was created
+    /// by [`SourceMap::split`].
+    pub(crate) fn push_from(&mut self, parent: &SourceMap, index: usize) {
+        self.lines.push(parent.location(index));
+    }
+
+    /// Insert lines that do not map to any file before the line at `index`.
+    pub(crate) fn insert_generated(&mut self, index: usize, count: usize) {
+        let index = index.min(self.lines.len());
+        self.lines.splice(index..index, vec![None; count]);
+    }
+}
+
+/// Preprocessed source text, along with where each of its lines was read from.
+#[derive(Debug, Default)]
+pub(crate) struct MappedSource {
+    pub(crate) text: String,
+    pub(crate) map: SourceMap,
+}
+
+impl SourceOutput for MappedSource {
+    fn push_line(&mut self, str: &str) {
+        self.text.push_line(str);
+        self.map.lines.push(None);
+    }
+
+    fn push_source_line(&mut self, str: &str, file: &Path, line_no: usize) {
+        self.text.push_line(str);
+        self.map.push(file, line_no);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{ShaderDefine, ShaderSource};
+    use librashader_presets::fs::MemoryFileSystem;
+    use std::path::Path;
+
+    #[test]
+    fn maps_lines_to_files() {
+        let mut fs = MemoryFileSystem::new();
+        fs.insert(
+            "/shaders/mapped.slang",
+            concat!(
+                "\n",
+                "#version 450\n",
+                "#include \"common.inc\"\n",
+                "#pragma stage vertex\n",
+                "void main() { gl_Position = vec4(0.0); }\n",
+                "#pragma stage fragment\n",
+                "layout(location = 0) out vec4 FragColor;\n",
+                "void main() { FragColor = vec4(SCALE); }\n",
+            ),
+        );
+        fs.insert(
+            "/shaders/common.inc",
+            "\n\n#define SCALE 1.0\nconst float OFFSET = 0.5;\n",
+        );
+
+        let defines = [ShaderDefine::new("USE_FAST_PATH", "1")];
+        let source =
+            ShaderSource::load_with_defines("/shaders/mapped.slang", &fs, &defines).unwrap();
+
+        let find = |text: &str, needle: &str| {
+            text.lines()
+                .position(|line| line.contains(needle))
+                .map(|index| index + 1)
+                .unwrap()
+        };
+        let shader = Path::new("/shaders/mapped.slang");
+        let common = Path::new("/shaders/common.inc");
+
+        assert_eq!(Some((shader, 2)), source.vertex_map.lookup(1));
+        let define = find(&source.vertex, "#define USE_FAST_PATH");
+        assert_eq!(None, source.vertex_map.lookup(define));
+
+        for (text, map) in [
+            (&source.vertex, &source.vertex_map),
+            (&source.fragment, &source.fragment_map),
+        ] {
+            assert_eq!(text.lines().count(), map.len());
+            assert_eq!(Some((common, 3)), map.lookup(find(text, "#define SCALE")));
+            assert_eq!(Some((common, 4)), map.lookup(find(text, "OFFSET")));
+        }
+
+        let vertex_main = find(&source.vertex, "gl_Position");
+        assert_eq!(Some((shader, 5)), source.vertex_map.lookup(vertex_main));
+        let fragment_main = find(&source.fragment, "FragColor = ");
+        assert_eq!(Some((shader, 8)), source.fragment_map.lookup(fragment_main));
+        assert_eq!(None, source.fragment_map.lookup(0));
+    }
+}
@@ -1,3 +1,4 @@
+use crate::source_map::SourceMap;
 use crate::{PreprocessError, SourceOutput};
 use std::str::FromStr;
 
@@ -23,13 +24,22 @@ impl FromStr for ActiveStage {
 pub(crate) struct ShaderOutput {
     pub(crate) fragment: String,
     pub(crate) vertex: String,
+    pub(crate) fragment_map: SourceMap,
+    pub(crate) vertex_map: SourceMap,
 }
 
-pub(crate) fn process_stages(source: &str) -> Result<ShaderOutput, PreprocessError> {
+pub(crate) fn process_stages(
+    source: &str,
+    map: &SourceMap,
+) -> Result<ShaderOutput, PreprocessError> {
     let mut active_stage = ActiveStage::Both;
-    let mut output = ShaderOutput::default();
+    let mut output = ShaderOutput {
+        fragment_map: map.split(),
+        vertex_map: map.split(),
+        ..ShaderOutput::default()
+    };
 
-    for line in source.lines() {
+    for (index, line) in source.lines().enumerate() {
         if let Some(stage) = line.strip_prefix("#pragma stage ") {
             let stage = stage.trim();
             active_stage = ActiveStage::from_str(stage)?;
@@ -46,12 +56,18 @@ pub(crate) fn process_stages(source: &str) -> Result<ShaderOutput, PreprocessErr
         match active_stage {
             ActiveStage::Both => {
                 output.fragment.push_line(line);
+                output.fragment_map.push_from(map, index);
                 output.vertex.push_line(line);
+                output.vertex_map.push_from(map, index);
             }
             ActiveStage::Fragment => {
                 output.fragment.push_line(line);
+                output.fragment_map.push_from(map, index);
+            }
+            ActiveStage::Vertex => {
+                output.vertex.push_line(line);
+                output.vertex_map.push_from(map, index);
             }
-            ActiveStage::Vertex => output.vertex.push_line(line),
         }
     }
 
@@ -0,0 +1,59 @@
+use crate::include::{read_source, IncludeResolver};
+use crate::{pragma, PreprocessError};
+use librashader_presets::{PresetValidationError, ShaderPreset};
+
+/// Check the shader preset for consistency, including whether every parameter set in the
+/// preset is declared by one of its shaders.
+///
+/// Shader sources are read with the given resolver to find the parameters they declare,
+/// but are not compiled. See [`ShaderPreset::validate`] for the other checks.
+pub fn validate_preset(
+    preset: &ShaderPreset,
+    resolver: &impl IncludeResolver,
+) -> Result<Vec<PresetValidationError>, PreprocessError> {
+    let mut declared = Vec::new();
+    for shader in &preset.shaders {
+        let source = read_source(&shader.name, resolver)?;
+        let meta = pragma::parse_pragma_meta(source)?;
+        declared.extend(meta.parameters.into_iter().map(|parameter| parameter.id));
+    }
+
+    let mut errors = preset.validate();
+    errors.extend(preset.validate_parameters(declared.iter().map(|id| id.as_str())));
+    Ok(errors)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::validate_preset;
+    use librashader_presets::fs::MemoryFileSystem;
+    use librashader_presets::{PresetValidationError, ShaderPreset, WildcardContext};
+
+    #[test]
+    fn finds_undeclared_parameters() {
+        let mut fs = MemoryFileSystem::new();
+        fs.insert(
+            "/shaders/stock.slang",
+            "#version 450\n#pragma parameter STRENGTH \"Strength\" 0.5 0.0 1.0 0.1\n",
+        );
+        fs.insert(
+            "/presets/stock.slangp",
+            "shaders = 2\nshader0 = ../shaders/stock.slang\nalias0 = Source\nparameters = \"STRENGTH;TYPO\"\nSTRENGTH = 1.0\nTYPO = 1.0\n",
+        );
+
+        let preset =
+            ShaderPreset::try_parse_with_fs("/presets/stock.slangp", WildcardContext::new(), &fs)
+                .unwrap();
+        assert_eq!(
+            vec![
+                PresetValidationError::MissingPass(1),
+                PresetValidationError::ReservedAlias {
+                    pass: 0,
+                    alias: "Source".into(),
+                },
+                PresetValidationError::UndeclaredParameter("TYPO".into()),
+            ],
+            validate_preset(&preset, &fs).unwrap()
+        );
+    }
+}
@@ -0,0 +1,651 @@
+use crate::error::PresetValidationError;
+use crate::{ParameterConfig, ShaderPassConfig, ShaderPreset, TextureConfig};
+
+/// Builds a [`ShaderPreset`] pass by pass.
+///
+/// Passes are addressed by their position in the filter chain. The builder keeps
+/// [`ShaderPassConfig::id`] and [`ShaderPreset::shader_count`] in sync with the position of
+/// each pass, and ensures every alias names exactly one pass and is not shadowed by a texture.
+#[derive(Debug, Clone)]
+pub struct ShaderPresetBuilder {
+    preset: ShaderPreset,
+}
+
+impl Default for ShaderPresetBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl From<ShaderPreset> for ShaderPresetBuilder {
+    /// Start building from an existing preset.
+    ///
+    /// Passes are ordered by their index, and renumbered to be contiguous.
+    fn from(mut preset: ShaderPreset) -> Self {
+        preset.shaders.sort_by_key(|shader| shader.id);
+        let mut builder = ShaderPresetBuilder { preset };
+        builder.renumber();
+        builder
+    }
+}
+
+impl ShaderPresetBuilder {
+    /// Create a builder for an empty preset.
+    pub fn new() -> Self {
+        ShaderPresetBuilder {
+            preset: ShaderPreset {
+                #[cfg(feature = "parse_legacy_glsl")]
+                feedback_pass: -1,
+                shader_count: 0,
+                shaders: Vec::new(),
+                textures: Vec::new(),
+                parameters: Vec::new(),
+            },
+        }
+    }
+
+    fn renumber(&mut self) {
+        for (index, shader) in self.preset.shaders.iter_mut().enumerate() {
+            shader.id = index as i32;
+        }
+        self.preset.shader_count = self.preset.shaders.len() as i32;
+    }
+
+    /// Check that the alias of a pass to be placed at `index` does not conflict.
+    ///
+    /// If `replacing` is set, the pass currently at `index` is being replaced and is ignored.
+    fn check_alias(
+        &self,
+        pass: &ShaderPassConfig,
+        index: usize,
+        replacing: bool,
+    ) -> Result<(), PresetValidationError> {
+        let Some(alias) = &pass.alias else {
+            return Ok(());
+        };
+
+        if let Some(existing) = self.preset.shaders.iter().position(|shader| {
+            shader.alias.as_ref() == Some(alias) && !(replacing && shader.id == index as i32)
+        }) {
+            return Err(PresetValidationError::DuplicateAlias {
+                alias: alias.clone(),
+                first: existing as i32,
+                second: index as i32,
+            });
+        }
+
+        if self
+            .preset
+            .textures
+            .iter()
+            .any(|texture| texture.name == *alias)
+        {
+            return Err(PresetValidationError::TextureShadowsAlias {
+                texture: alias.clone(),
+                pass: index as i32,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// The passes of the preset, in order.
+    pub fn passes(&self) -> &[ShaderPassConfig] {
+        &self.preset.shaders
+    }
+
+    /// Get the position of the pass with the given alias.
+    pub fn pass_index(&self, alias: &str) -> Option<usize> {
+        self.preset
+            .shaders
+            .iter()
+            .position(|shader| shader.alias.as_deref() == Some(alias))
+    }
+
+    /// Add a pass to the end of the filter chain.
+    ///
+    /// Fails if the alias of the pass is already used by another pass or a texture.
+    pub fn append_pass(
+        &mut self,
+        pass: ShaderPassConfig,
+    ) -> Result<&mut Self, PresetValidationError> {
+        self.insert_pass(self.preset.shaders.len(), pass)
+    }
+
+    /// Insert a pass at the given position in the filter chain, shifting every pass after it.
+    ///
+    /// Fails if the alias of the pass is already used by another pass or a texture.
+    ///
+    /// # Panics
+    /// Panics if `index` is greater than the number of passes.
+    pub fn insert_pass(
+        &mut self,
+        index: usize,
+        pass: ShaderPassConfig,
+    ) -> Result<&mut Self, PresetValidationError> {
+        assert!(
+            index <= self.preset.shaders.len(),
+            "pass index {index} is out of bounds"
+        );
+        self.check_alias(&pass, index, false)?;
+        self.preset.shaders.insert(index, pass);
+        self.renumber();
+        Ok(self)
+    }
+
+    /// Remove the pass at the given position from the filter chain, shifting every pass after it.
+    pub fn remove_pass(&mut self, index: usize) -> Option<ShaderPassConfig> {
+        if index >= self.preset.shaders.len() {
+            return None;
+        }
+        let pass = self.preset.shaders.remove(index);
+        self.renumber();
+        Some(pass)
+    }
+
+    /// Move the pass at position `from` to position `to`, shifting the passes in between.
+    ///
+    /// # Panics
+    /// Panics if either position is out of bounds.
+    pub fn move_pass(&mut self, from: usize, to: usize) -> &mut Self {
+        let len = self.preset.shaders.len();
+        assert!(
+            from < len && to < len,
+            "pass index {} is out of bounds",
+            from.max(to)
+        );
+        let pass = self.preset.shaders.remove(from);
+        self.preset.shaders.insert(to, pass);
+        self.renumber();
+        self
+    }
+
+    /// Change the options of the pass at the given position.
+    ///
+    /// The index of the pass is restored after `f` returns. Fails without changing the pass
+    /// if its new alias is already used by another pass or a texture.
+    ///
+    /// # Panics
+    /// Panics if `index` is out of bounds.
+    pub fn update_pass(
+        &mut self,
+        index: usize,
+        f: impl FnOnce(&mut ShaderPassConfig),
+    ) -> Result<&mut Self, PresetValidationError> {
+        let mut pass = self.preset.shaders[index].clone();
+        f(&mut pass);
+        self.check_alias(&pass, index, true)?;
+
+        self.preset.shaders[index] = pass;
+        self.renumber();
+        Ok(self)
+    }
+
+    /// Add a lookup texture, replacing any texture with the same name.
+    ///
+    /// Fails if the name of the texture is the alias of a pass.
+    pub fn add_texture(
+        &mut self,
+        texture: TextureConfig,
+    ) -> Result<&mut Self, PresetValidationError> {
+        if let Some(pass) = self.pass_index(&texture.name) {
+            return Err(PresetValidationError::TextureShadowsAlias {
+                texture: texture.name,
+                pass: pass as i32,
+            });
+        }
+
+        match self
+            .preset
+            .textures
+            .iter_mut()
+            .find(|existing| existing.name == texture.name)
+        {
+            Some(existing) => *existing = texture,
+            None => self.preset.textures.push(texture),
+        }
+        Ok(self)
+    }
+
+    /// Remove the lookup texture with the given name.
+    pub fn remove_texture(&mut self, name: &str) -> Option<TextureConfig> {
+        let index = self
+            .preset
+            .textures
+            .iter()
+            .position(|texture| texture.name == name)?;
+        Some(self.preset.textures.remove(index))
+    }
+
+    /// Set the value of a parameter, replacing any value it was already set to.
+    pub fn set_parameter(&mut self, name: &str, value: f32) -> &mut Self {
+        match self
+            .preset
+            .parameters
+            .iter_mut()
+            .find(|parameter| parameter.name == name)
+        {
+            Some(parameter) => parameter.value = value,
+            None => self.preset.parameters.push(ParameterConfig {
+                name: name.into(),
+                value,
+            }),
+        }
+        self
+    }
+
+    /// Remove the value of a parameter, so the shader default is used instead.
+    pub fn remove_parameter(&mut self, name: &str) -> Option<f32> {
+        let index = self
+            .preset
+            .parameters
+            .iter()
+            .position(|parameter| parameter.name == name)?;
+        Some(self.preset.parameters.remove(index).value)
+    }
+
+    /// Check that the passes and textures of `other` can be inserted at `index` without
+    /// changing what any pass samples.
+    ///
+    /// Shaders refer to passes and textures by name, so renaming an alias or dropping a
+    /// texture of either preset would silently bind a different texture.
+    fn check_preset(
+        &self,
+        index: usize,
+        other: &ShaderPreset,
+    ) -> Result<(), PresetValidationError> {
+        let mut passes = other.shaders.clone();
+        passes.sort_by_key(|shader| shader.id);
+        let inserted = passes.len();
+        // the position of an existing pass in the merged filter chain.
+        let position = |existing: usize| {
+            if existing < index {
+                existing as i32
+            } else {
+                (existing + inserted) as i32
+            }
+        };
+
+        for (offset, pass) in passes.iter().enumerate() {
+            let Some(alias) = &pass.alias else {
+                continue;
+            };
+            let pass = (index + offset) as i32;
+            if let Some(existing) = self.pass_index(alias) {
+                let (first, second) = if position(existing) < pass {
+                    (position(existing), pass)
+                } else {
+                    (pass, position(existing))
+                };
+                return Err(PresetValidationError::DuplicateAlias {
+                    alias: alias.clone(),
+                    first,
+                    second,
+                });
+            }
+            if self
+                .preset
+                .textures
+                .iter()
+                .any(|texture| texture.name == *alias)
+            {
+                return Err(PresetValidationError::TextureShadowsAlias {
+                    texture: alias.clone(),
+                    pass,
+                });
+            }
+        }
+
+        for texture in &other.textures {
+            if let Some(existing) = self.pass_index(&texture.name) {
+                return Err(PresetValidationError::TextureShadowsAlias {
+                    texture: texture.name.clone(),
+                    pass: position(existing),
+                });
+            }
+            if self
+                .preset
+                .textures
+                .iter()
+                .any(|existing| existing.name == texture.name && existing != texture)
+            {
+                return Err(PresetValidationError::ConflictingTexture(
+                    texture.name.clone(),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn insert_preset(
+        &mut self,
+        index: usize,
+        other: &ShaderPreset,
+    ) -> Result<&mut Self, PresetValidationError> {
+        self.check_preset(index, other)?;
+
+        for texture in &other.textures {
+            if !self.preset.textures.contains(texture) {
+                self.preset.textures.push(texture.clone());
+            }
+        }
+
+        let mut passes = other.shaders.clone();
+        passes.sort_by_key(|shader| shader.id);
+        for (offset, pass) in passes.into_iter().enumerate() {
+            self.preset.shaders.insert(index + offset, pass);
+        }
+
+        #[cfg(feature = "parse_legacy_glsl")]
+        {
+            let inserted = other.shaders.len() as i32;
+            if self.preset.feedback_pass >= index as i32 {
+                self.preset.feedback_pass += inserted;
+            } else if self.preset.feedback_pass < 0 && other.feedback_pass >= 0 {
+                self.preset.feedback_pass = other.feedback_pass + index as i32;
+            }
+        }
+
+        for parameter in &other.parameters {
+            if !self
+                .preset
+                .parameters
+                .iter()
+                .any(|existing| existing.name == parameter.name)
+            {
+                self.preset.parameters.push(parameter.clone());
+            }
+        }
+
+        self.renumber();
+        Ok(self)
+    }
+
+    /// Add every pass of another preset to the end of the filter chain.
+    ///
+    /// Shaders sample passes and textures by name, so names are never changed to resolve
+    /// a conflict. Instead, the builder is left unchanged and an error is returned if:
+    /// * An added pass has the same alias as an existing pass or texture.
+    /// * An added texture has the same name as an existing pass.
+    /// * An added texture has the same name as an existing texture, but a different
+    ///   configuration. Textures that are configured the same are shared.
+    ///
+    /// A parameter that is set by both presets keeps the value it already has in the builder.
+    pub fn append_preset(
+        &mut self,
+        other: &ShaderPreset,
+    ) -> Result<&mut Self, PresetValidationError> {
+        self.insert_preset(self.preset.shaders.len(), other)
+    }
+
+    /// Add every pass of another preset to the start of the filter chain.
+    ///
+    /// Conflicts are handled the same way as [`append_preset`](Self::append_preset).
+    pub fn prepend_preset(
+        &mut self,
+        other: &ShaderPreset,
+    ) -> Result<&mut Self, PresetValidationError> {
+        self.insert_preset(0, other)
+    }
+
+    /// Build the shader preset.
+    pub fn build(&self) -> ShaderPreset {
+        self.preset.clone()
+    }
+}
+
+impl ShaderPreset {
+    /// Create a builder to modify a copy of this preset.
+    pub fn to_builder(&self) -> ShaderPresetBuilder {
+        ShaderPresetBuilder::from(self.clone())
+    }
+
+    /// Create a preset that runs the passes of this preset, followed by the passes of `other`.
+    ///
+    /// See [`ShaderPresetBuilder::append_preset`] for how conflicts between the presets
+    /// are handled.
+    pub fn append(&self, other: &ShaderPreset) -> Result<ShaderPreset, PresetValidationError> {
+        Ok(self.to_builder().append_preset(other)?.build())
+    }
+
+    /// Create a preset that runs the passes of `other`, followed by the passes of this preset.
+    ///
+    /// See [`ShaderPresetBuilder::append_preset`] for how conflicts between the presets
+    /// are handled.
+    pub fn prepend(&self, other: &ShaderPreset) -> Result<ShaderPreset, PresetValidationError> {
+        Ok(self.to_builder().prepend_preset(other)?.build())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{PresetValidationError, ShaderPassConfig, ShaderPresetBuilder, TextureConfig};
+    use librashader_common::{FilterMode, WrapMode};
+    use std::path::PathBuf;
+
+    fn pass(name: &str, alias: Option<&str>) -> ShaderPassConfig {
+        let mut pass = ShaderPassConfig::new(format!("{name}.slang"));
+        pass.alias = alias.map(Into::into);
+        pass
+    }
+
+    fn texture(name: &str) -> TextureConfig {
+        TextureConfig {
+            name: name.into(),
+            path: PathBuf::from(format!("{name}.png")),
+            wrap_mode: WrapMode::ClampToBorder,
+            filter_mode: FilterMode::Nearest,
+            mipmap: false,
+        }
+    }
+
+    fn names(builder: &ShaderPresetBuilder) -> Vec<(i32, PathBuf)> {
+        builder
+            .passes()
+            .iter()
+            .map(|pass| (pass.id, pass.name.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn keeps_indices_in_sync() {
+        let mut builder = ShaderPresetBuilder::new();
+        builder
+            .append_pass(pass("crt", Some("CRT")))
+            .unwrap()
+            .append_pass(pass("color", None))
+            .unwrap()
+            .insert_pass(0, pass("upscale", None))
+            .unwrap();
+
+        assert_eq!(
+            vec![
+                (0, PathBuf::from("upscale.slang")),
+                (1, PathBuf::from("crt.slang")),
+                (2, PathBuf::from("color.slang")),
+            ],
+            names(&builder)
+        );
+        assert_eq!(Some(1), builder.pass_index("CRT"));
+
+        builder.move_pass(2, 0);
+        assert_eq!(Some(2), builder.pass_index("CRT"));
+
+        let removed = builder.remove_pass(1).unwrap();
+        assert_eq!(PathBuf::from("upscale.slang"), removed.name);
+
+        let preset = builder.build();
+        assert_eq!(2, preset.shader_count);
+        assert!(preset.validate().is_empty());
+        assert_eq!(
+            vec![
+                (0, PathBuf::from("color.slang")),
+                (1, PathBuf::from("crt.slang")),
+            ],
+            names(&preset.to_builder())
+        );
+    }
+
+    #[test]
+    fn rejects_conflicting_aliases() {
+        let mut builder = ShaderPresetBuilder::new();
+        builder.append_pass(pass("crt", Some("CRT"))).unwrap();
+        builder.add_texture(texture("Mask")).unwrap();
+
+        assert_eq!(
+            PresetValidationError::DuplicateAlias {
+                alias: "CRT".into(),
+                first: 0,
+                second: 1,
+            },
+            builder.append_pass(pass("other", Some("CRT"))).unwrap_err()
+        );
+        assert_eq!(
+            PresetValidationError::TextureShadowsAlias {
+                texture: "Mask".into(),
+                pass: 0,
+            },
+            builder
+                .update_pass(0, |pass| pass.alias = Some("Mask".into()))
+                .unwrap_err()
+        );
+        assert_eq!(Some(0), builder.pass_index("CRT"));
+        assert!(builder.add_texture(texture("CRT")).is_err());
+
+        builder.remove_texture("Mask").unwrap();
+        builder
+            .update_pass(0, |pass| pass.alias = Some("Mask".into()))
+            .unwrap();
+        assert_eq!(Some(0), builder.pass_index("Mask"));
+    }
+
+    #[test]
+    fn sets_parameters() {
+        let mut builder = ShaderPresetBuilder::new();
+        builder
+            .set_parameter("Strength", 0.5)
+            .set_parameter("Curvature", 1.0)
+            .set_parameter("Strength", 0.25);
+        assert_eq!(Some(1.0), builder.remove_parameter("Curvature"));
+
+        let preset = builder.build();
+        assert_eq!(1, preset.parameters.len());
+        assert_eq!(0.25, preset.parameters[0].value);
+    }
+
+    #[test]
+    fn composes_presets() {
+        let mut current = ShaderPresetBuilder::new();
+        current
+            .append_pass(pass("crt", Some("CRT")))
+            .unwrap()
+            .add_texture(texture("Mask"))
+            .unwrap()
+            .set_parameter("Strength", 0.5);
+        let current = current.build();
+
+        let mut other = ShaderPresetBuilder::new();
+        other
+            .append_pass(pass("upscale", Some("Upscale")))
+            .unwrap()
+            .append_pass(pass("sharpen", None))
+            .unwrap()
+            .add_texture(texture("Mask"))
+            .unwrap()
+            .add_texture(texture("Noise"))
+            .unwrap()
+            .set_parameter("Strength", 1.0)
+            .set_parameter("Sharpness", 2.0);
+        let other = other.build();
+
+        let appended = current.append(&other).unwrap();
+        assert_eq!(3, appended.shader_count);
+        assert!(appended.validate().is_empty());
+        let aliases: Vec<(i32, Option<&str>)> = appended
+            .shaders
+            .iter()
+            .map(|pass| (pass.id, pass.alias.as_deref()))
+            .collect();
+        assert_eq!(
+            vec![(0, Some("CRT")), (1, Some("Upscale")), (2, None)],
+            aliases
+        );
+        let textures: Vec<&str> = appended.textures.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(vec!["Mask", "Noise"], textures);
+        let parameters: Vec<(&str, f32)> = appended
+            .parameters
+            .iter()
+            .map(|p| (p.name.as_str(), p.value))
+            .collect();
+        assert_eq!(vec![("Strength", 0.5), ("Sharpness", 2.0)], parameters);
+
+        let prepended = current.prepend(&other).unwrap();
+        assert!(prepended.validate().is_empty());
+        assert_eq!(
+            vec![
+                (0, PathBuf::from("upscale.slang")),
+                (1, PathBuf::from("sharpen.slang")),
+                (2, PathBuf::from("crt.slang")),
+            ],
+            names(&prepended.to_builder())
+        );
+        assert_eq!(Some("CRT"), prepended.shaders[2].alias.as_deref());
+    }
+
+    #[test]
+    fn rejects_conflicting_presets() {
+        let mut current = ShaderPresetBuilder::new();
+        current
+            .append_pass(pass("crt", Some("Blur")))
+            .unwrap()
+            .add_texture(texture("Mask"))
+            .unwrap();
+
+        let mut other = ShaderPresetBuilder::new();
+        other
+            .append_pass(pass("sharpen", None))
+            .unwrap()
+            .append_pass(pass("upscale", Some("Blur")))
+            .unwrap();
+        assert_eq!(
+            PresetValidationError::DuplicateAlias {
+                alias: "Blur".into(),
+                first: 0,
+                second: 2,
+            },
+            current.append_preset(&other.build()).unwrap_err()
+        );
+        assert_eq!(
+            PresetValidationError::DuplicateAlias {
+                alias: "Blur".into(),
+                first: 1,
+                second: 2,
+            },
+            current.prepend_preset(&other.build()).unwrap_err()
+        );
+
+        let mut other = ShaderPresetBuilder::new();
+        let mut mask = texture("Mask");
+        mask.mipmap = true;
+        other.add_texture(mask).unwrap();
+        assert_eq!(
+            PresetValidationError::ConflictingTexture("Mask".into()),
+            current.append_preset(&other.build()).unwrap_err()
+        );
+
+        let mut other = ShaderPresetBuilder::new();
+        other.add_texture(texture("Blur")).unwrap();
+        assert_eq!(
+            PresetValidationError::TextureShadowsAlias {
+                texture: "Blur".into(),
+                pass: 0,
+            },
+            current.append_preset(&other.build()).unwrap_err()
+        );
+
+        let preset = current.build();
+        assert_eq!(1, preset.shader_count);
+        assert_eq!(1, preset.textures.len());
+    }
+}
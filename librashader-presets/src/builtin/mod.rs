@@ -0,0 +1,237 @@
+//! Shaders embedded in librashader, that can be used without any files on disk.
+use crate::{ShaderPassConfig, ShaderPreset, ShaderPresetBuilder};
+use librashader_common::FilterMode;
+use std::path::{Path, PathBuf};
+
+/// The prefix of the path of a built-in shader.
+pub const BUILTIN_PREFIX: &str = "builtin:";
+
+/// A shader that is embedded in librashader.
+///
+/// Built-in shaders are referred to with a path starting with `builtin:`, such as
+/// `shader0 = builtin:stock.slang` in a preset, or [`BuiltinShader::path`]. They are
+/// always available, and are never read from a filesystem.
+///
+/// When a preset refers to a `stock.slang` shader that does not exist, the built-in
+/// [`BuiltinShader::Stock`] is used instead, and reported as a
+/// [`SearchRootSubstitution`](crate::SearchRootSubstitution), or as a
+/// [`PresetDiagnosticKind::BuiltinFallback`](crate::PresetDiagnosticKind::BuiltinFallback)
+/// when parsing leniently. This keeps presets that expect `stock.slang` next to them
+/// loadable. Other missing shaders are never replaced, even if they have the same file name
+/// as a built-in shader.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum BuiltinShader {
+    /// `stock.slang`, which passes the source through unchanged.
+    Stock,
+    /// `sharp-bilinear.slang`, which scales with an integer nearest-neighbour prescale,
+    /// then bilinear filtering.
+    SharpBilinear,
+    /// `gamma.slang`, which converts between the gamma of the content and the display.
+    Gamma,
+}
+
+impl BuiltinShader {
+    /// Every built-in shader.
+    pub const ALL: [BuiltinShader; 3] = [
+        BuiltinShader::Stock,
+        BuiltinShader::SharpBilinear,
+        BuiltinShader::Gamma,
+    ];
+
+    /// The file name of the shader.
+    pub fn file_name(self) -> &'static str {
+        match self {
+            BuiltinShader::Stock => "stock.slang",
+            BuiltinShader::SharpBilinear => "sharp-bilinear.slang",
+            BuiltinShader::Gamma => "gamma.slang",
+        }
+    }
+
+    /// The path to refer to the shader by, such as `builtin:stock.slang`.
+    pub fn path(self) -> PathBuf {
+        PathBuf::from(format!("{BUILTIN_PREFIX}{}", self.file_name()))
+    }
+
+    /// The source text of the shader.
+    pub fn source(self) -> &'static str {
+        match self {
+            BuiltinShader::Stock => include_str!("stock.slang"),
+            BuiltinShader::SharpBilinear => include_str!("sharp-bilinear.slang"),
+            BuiltinShader::Gamma => include_str!("gamma.slang"),
+        }
+    }
+
+    /// Get the built-in shader with the given file name.
+    pub fn from_file_name(name: &str) -> Option<BuiltinShader> {
+        Self::ALL
+            .into_iter()
+            .find(|shader| shader.file_name() == name)
+    }
+
+    /// Get the built-in shader the given path refers to, if it starts with `builtin:`.
+    pub fn from_path(path: &Path) -> Option<BuiltinShader> {
+        path.to_str()?
+            .strip_prefix(BUILTIN_PREFIX)
+            .and_then(Self::from_file_name)
+    }
+
+    /// Create the configuration for a shader pass running this shader.
+    ///
+    /// Passes that need linear filtering to work have it enabled.
+    pub fn pass(self) -> ShaderPassConfig {
+        let mut pass = ShaderPassConfig::new(self.path());
+        if self == BuiltinShader::SharpBilinear {
+            pass.filter = FilterMode::Linear;
+        }
+        pass
+    }
+}
+
+/// Whether the path refers to a built-in shader, whether or not the shader exists.
+pub(crate) fn is_builtin_path(path: &str) -> bool {
+    path.starts_with(BUILTIN_PREFIX)
+}
+
+impl ShaderPreset {
+    /// Create a preset with a single `stock.slang` pass, that is always loadable.
+    ///
+    /// This can be used as a fallback when a preset fails to load.
+    pub fn passthrough() -> ShaderPreset {
+        let mut builder = ShaderPresetBuilder::new();
+        // NOPANIC: the pass has no alias.
+        builder.append_pass(BuiltinShader::Stock.pass()).unwrap();
+        builder.build()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::builtin::BuiltinShader;
+    use crate::fs::MemoryFileSystem;
+    use crate::{PresetDiagnosticKind, SearchRootSubstitution, ShaderPreset, WildcardContext};
+    use librashader_common::FilterMode;
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn resolves_builtin_shaders() {
+        let mut fs = MemoryFileSystem::new();
+        fs.insert(
+            "presets/builtin.slangp",
+            concat!(
+                "shaders = 3\n",
+                "shader0 = stock.slang\n",
+                "shader1 = builtin:sharp-bilinear.slang\n",
+                "filter_linear1 = true\n",
+                "shader2 = builtin:gamma.slang\n",
+            ),
+        );
+
+        let preset =
+            ShaderPreset::try_parse_with_fs("presets/builtin.slangp", WildcardContext::new(), &fs)
+                .unwrap();
+        let names: Vec<&Path> = preset.shaders.iter().map(|s| s.name.as_path()).collect();
+        assert_eq!(
+            vec![
+                Path::new("builtin:stock.slang"),
+                Path::new("builtin:sharp-bilinear.slang"),
+                Path::new("builtin:gamma.slang"),
+            ],
+            names
+        );
+
+        let written = preset.to_slangp_string("/presets");
+        assert!(written.contains("shader1 = \"builtin:sharp-bilinear.slang\""));
+
+        fs.insert(
+            "presets/missing.slangp",
+            "shaders = 1\nshader0 = builtin:crt.slang\n",
+        );
+        let missing =
+            ShaderPreset::try_parse_with_fs("presets/missing.slangp", WildcardContext::new(), &fs);
+        assert!(missing.is_err());
+
+        // only stock.slang falls back to the built-in shader.
+        fs.insert(
+            "presets/gamma.slangp",
+            "shaders = 1\nshader0 = gamma.slang\n",
+        );
+        let gamma =
+            ShaderPreset::try_parse_with_fs("presets/gamma.slangp", WildcardContext::new(), &fs);
+        assert!(gamma.is_err());
+    }
+
+    #[test]
+    fn reports_builtin_fallbacks() {
+        let mut fs = MemoryFileSystem::new();
+        fs.insert(
+            "/presets/fallback.slangp",
+            "shaders = 2\nshader0 = ../stock.slang\nshader1 = gamma.slang\n",
+        );
+
+        let (preset, diagnostics) = ShaderPreset::try_parse_lenient(
+            "/presets/fallback.slangp",
+            WildcardContext::new(),
+            &fs,
+        )
+        .unwrap();
+        assert_eq!(PathBuf::from("builtin:stock.slang"), preset.shaders[0].name);
+        assert_eq!(
+            PathBuf::from("/presets/gamma.slang"),
+            preset.shaders[1].name
+        );
+        let kinds: Vec<PresetDiagnosticKind> = diagnostics.into_iter().map(|d| d.kind).collect();
+        assert_eq!(
+            vec![
+                PresetDiagnosticKind::BuiltinFallback {
+                    path: PathBuf::from("/presets/../stock.slang"),
+                    builtin: BuiltinShader::Stock,
+                },
+                PresetDiagnosticKind::MissingFile(PathBuf::from("/presets/gamma.slang")),
+            ],
+            kinds
+        );
+
+        fs.insert(
+            "/presets/fallback.slangp",
+            "shaders = 1\nshader0 = ../stock.slang\n",
+        );
+        let (_, substitutions) = ShaderPreset::try_parse_with_search_roots(
+            "/presets/fallback.slangp",
+            WildcardContext::new(),
+            &[],
+            &fs,
+        )
+        .unwrap();
+        assert_eq!(
+            vec![SearchRootSubstitution {
+                file: PathBuf::from("/presets/fallback.slangp"),
+                original: PathBuf::from("/presets/../stock.slang"),
+                root: PathBuf::from("builtin:"),
+                resolved: PathBuf::from("builtin:stock.slang"),
+            }],
+            substitutions
+        );
+    }
+
+    #[test]
+    fn builds_builtin_passes() {
+        let pass = BuiltinShader::SharpBilinear.pass();
+        assert_eq!(PathBuf::from("builtin:sharp-bilinear.slang"), pass.name);
+        assert_eq!(FilterMode::Linear, pass.filter);
+        assert_eq!(
+            Some(BuiltinShader::SharpBilinear),
+            BuiltinShader::from_path(&pass.name)
+        );
+        assert_eq!(
+            None,
+            BuiltinShader::from_path(Path::new("sharp-bilinear.slang"))
+        );
+
+        let preset = ShaderPreset::passthrough();
+        assert_eq!(1, preset.shaders.len());
+        assert_eq!(
+            Some(BuiltinShader::Stock),
+            BuiltinShader::from_path(&preset.shaders[0].name)
+        );
+    }
+}
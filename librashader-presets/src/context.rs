@@ -4,6 +4,7 @@
 //!
 //! Implements wildcard replacement of shader paths specified in
 //! [RetroArch#15023](https://github.com/libretro/RetroArch/pull/15023).
+use crate::fs::PresetFileSystem;
 use librashader_common::map::FastHashMap;
 use once_cell::sync::Lazy;
 use regex::bytes::Regex;
@@ -365,18 +366,143 @@ impl WildcardContext {
     }
 }
 
-pub(crate) fn apply_context(path: &mut PathBuf, context: &FastHashMap<String, String>) {
+/// The level of the auto-load preset hierarchy a preset was found at.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum AutoPresetLevel {
+    /// A preset for the game, at `<root>/<core>/<game>.<ext>`.
+    Game,
+    /// A preset for the directory the game is in, at `<root>/<core>/<content dir>.<ext>`.
+    ContentDirectory,
+    /// A preset for the core, at `<root>/<core>/<core>.<ext>`.
+    Core,
+    /// The global preset, at `<root>/global.<ext>`.
+    Global,
+}
+
+/// A preset found in the auto-load preset hierarchy.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct AutoPreset {
+    /// The path to the preset.
+    pub path: PathBuf,
+    /// The level of the hierarchy the preset was found at.
+    pub level: AutoPresetLevel,
+}
+
+impl WildcardContext {
+    fn last_item<'a, T>(&'a self, f: impl Fn(&'a ContextItem) -> Option<T>) -> Option<T> {
+        self.0.iter().rev().find_map(f)
+    }
+
+    /// Find the preset that RetroArch would automatically load for this context, in the
+    /// shader configuration directory at `root`.
+    ///
+    /// Like RetroArch, the game preset is preferred, then the content directory preset, then
+    /// the core preset, then the global preset. Levels that need a context item that is not
+    /// set, such as [`ContextItem::GameName`], are skipped.
+    ///
+    /// At each level, every [`ContextItem::VideoDriverPresetExtension`] in the context is tried
+    /// in order of precedence. If there is none, only `.slangp` presets are found.
+    pub fn find_auto_preset(
+        &self,
+        root: impl AsRef<Path>,
+        fs: &impl PresetFileSystem,
+    ) -> Option<AutoPreset> {
+        let root = root.as_ref();
+        let core = self.last_item(|item| match item {
+            ContextItem::CoreName(name) => Some(name.as_str()),
+            _ => None,
+        });
+        let game = self.last_item(|item| match item {
+            ContextItem::GameName(name) => Some(name.as_str()),
+            _ => None,
+        });
+        // RetroArch names content directory presets after the last component of the directory.
+        let content_directory = self.last_item(|item| match item {
+            ContextItem::ContentDirectory(dir) => {
+                Path::new(dir).file_name().and_then(|name| name.to_str())
+            }
+            _ => None,
+        });
+
+        let mut extensions: Vec<PresetExtension> = self
+            .0
+            .iter()
+            .rev()
+            .filter_map(|item| match item {
+                ContextItem::VideoDriverPresetExtension(extension) => Some(*extension),
+                _ => None,
+            })
+            .collect();
+        if extensions.is_empty() {
+            extensions.push(PresetExtension::Slangp);
+        }
+
+        let mut candidates = Vec::new();
+        if let Some(core) = core {
+            let core_dir = root.join(core);
+            if let Some(game) = game {
+                candidates.push((AutoPresetLevel::Game, core_dir.join(game)));
+            }
+            if let Some(content_directory) = content_directory {
+                candidates.push((
+                    AutoPresetLevel::ContentDirectory,
+                    core_dir.join(content_directory),
+                ));
+            }
+            candidates.push((AutoPresetLevel::Core, core_dir.join(core)));
+        }
+        candidates.push((AutoPresetLevel::Global, root.join("global")));
+
+        candidates.into_iter().find_map(|(level, stem)| {
+            extensions.iter().find_map(|extension| {
+                let mut path = stem.clone().into_os_string();
+                path.push(format!(".{extension}"));
+                let path = PathBuf::from(path);
+                fs.exists(&path).then_some(AutoPreset { path, level })
+            })
+        })
+    }
+}
+
+/// How the wildcards in a path of a shader preset were replaced.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WildcardResolution {
+    /// The preset file the path was written in, or `None` for the path of the root preset.
+    pub file: Option<PathBuf>,
+    /// The path with wildcards, before they were replaced.
+    pub original: PathBuf,
+    /// The keys of the wildcards that were replaced, such as `CORE`, in the order they appear.
+    pub wildcards: Vec<String>,
+    /// Every path that was tried, in order.
+    ///
+    /// The path with the wildcards replaced is tried first. If it does not exist, the original
+    /// path is tried as a fallback.
+    pub tried: Vec<PathBuf>,
+    /// The path that was used.
+    pub resolved: PathBuf,
+}
+
+/// Replace the wildcards in the path with the values in the context.
+///
+/// If no wildcards could be replaced, returns `None` and leaves the path as is.
+pub(crate) fn apply_context(
+    path: &mut PathBuf,
+    file: Option<&Path>,
+    context: &FastHashMap<String, String>,
+    fs: &dyn PresetFileSystem,
+) -> Option<WildcardResolution> {
     use std::ffi::{OsStr, OsString};
 
     static WILDCARD_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new("\\$([A-Z-_]+)\\$").unwrap());
     if context.is_empty() {
-        return;
+        return None;
     }
     // Don't want to do any extra work if there's no match.
     if !WILDCARD_REGEX.is_match(path.as_os_str().as_encoded_bytes()) {
-        return;
+        return None;
     }
 
+    let mut wildcards = Vec::new();
     let mut new_path = PathBuf::with_capacity(path.capacity());
     for component in path.components() {
         match component {
@@ -393,6 +519,7 @@ pub(crate) fn apply_context(path: &mut PathBuf, context: &FastHashMap<String, St
                             return caps[0].to_vec();
                         };
                         if let Some(replacement) = context.get(key) {
+                            wildcards.push(String::from(key));
                             return OsString::from(replacement.to_string()).into_encoded_bytes();
                         }
                         return caps[0].to_vec();
@@ -406,8 +533,93 @@ pub(crate) fn apply_context(path: &mut PathBuf, context: &FastHashMap<String, St
         }
     }
 
-    // If no wildcards are found within the path, or the path after replacing the wildcards does not exist on disk, the path returned will be unaffected.
-    if let Ok(true) = new_path.try_exists() {
+    if wildcards.is_empty() {
+        return None;
+    }
+
+    let original = path.clone();
+    let mut tried = vec![new_path.clone()];
+
+    // If no wildcards are found within the path, or the path after replacing the wildcards does not exist in the filesystem, the path returned will be unaffected.
+    if fs.exists(&new_path) {
         *path = new_path;
+    } else {
+        tried.push(original.clone());
+    }
+
+    Some(WildcardResolution {
+        file: file.map(Path::to_path_buf),
+        original,
+        wildcards,
+        tried,
+        resolved: path.clone(),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::context::{
+        AutoPreset, AutoPresetLevel, ContextItem, PresetExtension, WildcardContext,
+    };
+    use crate::fs::MemoryFileSystem;
+    use std::path::PathBuf;
+
+    #[test]
+    fn finds_auto_preset_by_priority() {
+        let mut fs = MemoryFileSystem::new();
+        fs.insert("shaders/global.slangp", "");
+        fs.insert("shaders/Snes9x/Snes9x.slangp", "");
+        fs.insert("shaders/Snes9x/snes.glslp", "");
+        fs.insert("shaders/Snes9x/Super Metroid.cgp", "");
+
+        let mut context = WildcardContext::new();
+        context.append_item(ContextItem::CoreName(String::from("Snes9x")));
+        context.append_item(ContextItem::GameName(String::from("Super Metroid")));
+        context.append_item(ContextItem::ContentDirectory(String::from("/roms/snes")));
+
+        let found = |context: &WildcardContext| context.find_auto_preset("/shaders", &fs);
+        assert_eq!(
+            Some(AutoPreset {
+                path: PathBuf::from("/shaders/Snes9x/Snes9x.slangp"),
+                level: AutoPresetLevel::Core,
+            }),
+            found(&context)
+        );
+
+        context.append_item(ContextItem::VideoDriverPresetExtension(
+            PresetExtension::Slangp,
+        ));
+        context.append_item(ContextItem::VideoDriverPresetExtension(
+            PresetExtension::Glslp,
+        ));
+        assert_eq!(
+            Some(AutoPreset {
+                path: PathBuf::from("/shaders/Snes9x/snes.glslp"),
+                level: AutoPresetLevel::ContentDirectory,
+            }),
+            found(&context)
+        );
+
+        context.append_item(ContextItem::VideoDriverPresetExtension(
+            PresetExtension::Cgp,
+        ));
+        assert_eq!(
+            Some(AutoPreset {
+                path: PathBuf::from("/shaders/Snes9x/Super Metroid.cgp"),
+                level: AutoPresetLevel::Game,
+            }),
+            found(&context)
+        );
+
+        let mut other_core = WildcardContext::new();
+        other_core.append_item(ContextItem::CoreName(String::from("bsnes")));
+        assert_eq!(
+            Some(AutoPreset {
+                path: PathBuf::from("/shaders/global.slangp"),
+                level: AutoPresetLevel::Global,
+            }),
+            found(&other_core)
+        );
+        assert_eq!(None, other_core.find_auto_preset("/other", &fs));
     }
 }
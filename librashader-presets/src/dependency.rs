@@ -0,0 +1,111 @@
+use crate::fs::normalize_path;
+use crate::ShaderPreset;
+use librashader_common::map::ShortString;
+use std::path::{Path, PathBuf};
+
+/// How a file was pulled into a shader preset.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DependencyKind {
+    /// A preset referenced with `#reference`.
+    Reference,
+    /// The source of the shader pass with the given index, declared with `shaderN`.
+    Shader(i32),
+    /// A file included with `#include` by a shader source or another included file.
+    ///
+    /// Includes are only known once shader sources are preprocessed, so these edges are
+    /// added by `librashader-preprocess`.
+    Include,
+    /// The LUT texture with the given name.
+    Texture(ShortString),
+}
+
+/// An edge in the dependency graph of a shader preset.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dependency {
+    /// How the file was pulled in.
+    pub kind: DependencyKind,
+    /// The file that pulled in the dependency.
+    pub parent: PathBuf,
+    /// The file that was pulled in.
+    pub path: PathBuf,
+}
+
+/// Every file a shader preset depends on, and the edges that pulled each one in.
+///
+/// Paths in the graph are absolute and lexically normalized, so the same file always
+/// has the same path, no matter how it was referred to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DependencyGraph {
+    /// The path of the root preset.
+    ///
+    /// For presets parsed from a string, this is the directory the preset was resolved against.
+    pub root: PathBuf,
+    /// The edges of the graph, in the order the files were pulled in.
+    pub dependencies: Vec<Dependency>,
+}
+
+impl DependencyGraph {
+    /// Iterate over every file in the graph, including the root preset, without duplicates.
+    pub fn files(&self) -> impl Iterator<Item = &Path> {
+        let mut seen: Vec<&Path> = Vec::new();
+        std::iter::once(self.root.as_path())
+            .chain(self.dependencies.iter().map(|d| d.path.as_path()))
+            .filter(move |path| {
+                if seen.contains(path) {
+                    false
+                } else {
+                    seen.push(path);
+                    true
+                }
+            })
+    }
+
+    /// Iterate over the edges that pulled in the file at the given path.
+    pub fn dependents<'a>(&'a self, path: &'a Path) -> impl Iterator<Item = &'a Dependency> {
+        self.dependencies.iter().filter(move |d| d.path == path)
+    }
+
+    /// Iterate over the edges of the files directly pulled in by the file at the given path.
+    pub fn dependencies_of<'a>(&'a self, parent: &'a Path) -> impl Iterator<Item = &'a Dependency> {
+        self.dependencies.iter().filter(move |d| d.parent == parent)
+    }
+
+    /// Get the indices of every shader pass affected by a change to the file at the given path.
+    ///
+    /// A pass is affected if its shader source, or any file it transitively includes, is the
+    /// file at the given path.
+    pub fn affected_passes(&self, path: &Path) -> Vec<i32> {
+        let mut passes = Vec::new();
+        let mut queue = vec![path];
+        let mut visited: Vec<&Path> = Vec::new();
+        while let Some(path) = queue.pop() {
+            if visited.contains(&path) {
+                continue;
+            }
+            visited.push(path);
+            for dependency in self.dependents(path) {
+                match dependency.kind {
+                    DependencyKind::Shader(index) if !passes.contains(&index) => passes.push(index),
+                    DependencyKind::Include => queue.push(&dependency.parent),
+                    _ => {}
+                }
+            }
+        }
+        passes.sort();
+        passes
+    }
+
+    /// Remove shader and texture edges that were overridden, and are not used by the preset.
+    pub(crate) fn retain_used(&mut self, preset: &ShaderPreset) {
+        self.dependencies
+            .retain(|dependency| match &dependency.kind {
+                DependencyKind::Shader(index) => preset.shaders.iter().any(|shader| {
+                    shader.id == *index && normalize_path(&shader.name) == dependency.path
+                }),
+                DependencyKind::Texture(name) => preset.textures.iter().any(|texture| {
+                    texture.name == *name && normalize_path(&texture.path) == dependency.path
+                }),
+                DependencyKind::Reference | DependencyKind::Include => true,
+            });
+    }
+}
@@ -0,0 +1,96 @@
+use crate::BuiltinShader;
+use std::fmt::{Display, Formatter};
+use std::path::PathBuf;
+
+/// The kind of problem found in a shader preset that was parsed leniently.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PresetDiagnosticKind {
+    /// A file referred to by the preset could not be found.
+    ///
+    /// Missing `#reference`d presets are skipped. Missing shaders and textures are kept in
+    /// the parsed preset, and will fail to load.
+    MissingFile(PathBuf),
+    /// The key is not known, and was ignored.
+    UnknownKey(String),
+    /// The key was assigned more than once in the same file.
+    DuplicateKey(String),
+    /// The value could not be parsed, and was replaced with another value.
+    CoercedValue {
+        /// The key the value was assigned to.
+        key: String,
+        /// The value as written in the preset.
+        value: String,
+        /// The value it was replaced with.
+        coerced: String,
+    },
+    /// A `stock.slang` shader referred to by the preset could not be found, and the built-in
+    /// shader was used instead.
+    BuiltinFallback {
+        /// The path of the shader that could not be found.
+        path: PathBuf,
+        /// The built-in shader that was used instead.
+        builtin: BuiltinShader,
+    },
+    /// The value could not be parsed, and was ignored. The default for the key is used instead.
+    InvalidValue {
+        /// The key the value was assigned to.
+        key: String,
+        /// The value as written in the preset.
+        value: String,
+    },
+}
+
+/// A problem found in a shader preset that was parsed leniently.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PresetDiagnostic {
+    /// What the problem was.
+    pub kind: PresetDiagnosticKind,
+    /// The preset file the problem was found in.
+    pub file: PathBuf,
+    /// The line the problem was found on, starting from 1.
+    pub row: u32,
+    /// The column the problem was found at, starting from 1.
+    pub col: usize,
+}
+
+impl Display for PresetDiagnosticKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PresetDiagnosticKind::MissingFile(path) => {
+                write!(f, "file not found: {}", path.display())
+            }
+            PresetDiagnosticKind::BuiltinFallback { path, builtin } => write!(
+                f,
+                "file not found: {}, using {}",
+                path.display(),
+                builtin.path().display()
+            ),
+            PresetDiagnosticKind::UnknownKey(key) => write!(f, "unknown key `{key}`"),
+            PresetDiagnosticKind::DuplicateKey(key) => write!(f, "duplicate key `{key}`"),
+            PresetDiagnosticKind::CoercedValue {
+                key,
+                value,
+                coerced,
+            } => write!(
+                f,
+                "invalid value \"{value}\" for `{key}`, using \"{coerced}\""
+            ),
+            PresetDiagnosticKind::InvalidValue { key, value } => {
+                write!(f, "invalid value \"{value}\" for `{key}` was ignored")
+            }
+        }
+    }
+}
+
+impl Display for PresetDiagnostic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}: {}",
+            self.file.display(),
+            self.row,
+            self.col,
+            self.kind
+        )
+    }
+}
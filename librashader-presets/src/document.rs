@@ -0,0 +1,307 @@
+use crate::error::{DocumentEditError, ParsePresetError};
+use crate::parse::do_lex;
+use std::fmt::{Display, Formatter};
+use std::ops::Range;
+use std::path::Path;
+
+/// A single `key = value` assignment or `#reference` in a [`PresetDocument`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DocumentEntry {
+    key: Range<usize>,
+    value: Range<usize>,
+}
+
+impl DocumentEntry {
+    /// The byte range of the key in the document text.
+    ///
+    /// For references, the key is `#reference`.
+    pub fn key_span(&self) -> Range<usize> {
+        self.key.clone()
+    }
+
+    /// The byte range of the value in the document text, not including quotes.
+    pub fn value_span(&self) -> Range<usize> {
+        self.value.clone()
+    }
+}
+
+/// The text of a shader preset, with the location of every key and value in it.
+///
+/// Unlike [`ShaderPreset`](crate::ShaderPreset), a document keeps comments, ordering and
+/// formatting. Values can be edited in place, and only the edited bytes change when the
+/// document is written back out.
+///
+/// A document is a single file. References are not followed, and values are not checked.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PresetDocument {
+    text: String,
+    entries: Vec<DocumentEntry>,
+}
+
+impl PresetDocument {
+    /// Parse the text of a shader preset into a document.
+    ///
+    /// A document is not read from a file, so lexer errors are reported with an empty file.
+    pub fn parse(text: impl Into<String>) -> Result<PresetDocument, ParsePresetError> {
+        let text = text.into();
+        let entries = do_lex(&text, Path::new(""))?
+            .into_iter()
+            .map(|token| {
+                let key = token.key.location_offset();
+                let value = token.value.location_offset();
+                // unquoted values can include trailing whitespace before a comment.
+                let fragment = token.value.fragment();
+                let value_len = if text[value + fragment.len()..].starts_with('"') {
+                    fragment.len()
+                } else {
+                    fragment.trim_end().len()
+                };
+                DocumentEntry {
+                    key: key..key + token.key.fragment().len(),
+                    value: value..value + value_len,
+                }
+            })
+            .collect();
+        Ok(PresetDocument { text, entries })
+    }
+
+    /// The text of the document.
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    /// Every entry in the document, in the order they are written.
+    pub fn entries(&self) -> &[DocumentEntry] {
+        &self.entries
+    }
+
+    /// The key of an entry.
+    pub fn key(&self, entry: &DocumentEntry) -> &str {
+        &self.text[entry.key_span()]
+    }
+
+    /// The value of an entry, without quotes.
+    pub fn value(&self, entry: &DocumentEntry) -> &str {
+        &self.text[entry.value_span()]
+    }
+
+    /// Find the entry for the given key.
+    ///
+    /// If the key is assigned more than once, this is the first assignment, which is the one
+    /// that takes effect when the preset is parsed.
+    pub fn entry(&self, key: &str) -> Option<&DocumentEntry> {
+        self.entries.iter().find(|entry| self.key(entry) == key)
+    }
+
+    /// Get the value of the given key.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entry(key).map(|entry| self.value(entry))
+    }
+
+    /// Replace the bytes in `range`, and move every span after it.
+    fn splice(&mut self, range: Range<usize>, replacement: &str) {
+        let removed = range.len();
+        let end = range.end;
+        self.text.replace_range(range, replacement);
+
+        let shift = |offset: &mut usize| {
+            if *offset >= end {
+                *offset = *offset - removed + replacement.len();
+            }
+        };
+        for entry in &mut self.entries {
+            shift(&mut entry.key.start);
+            shift(&mut entry.key.end);
+            shift(&mut entry.value.start);
+            shift(&mut entry.value.end);
+        }
+    }
+
+    /// Set the value of the given key.
+    ///
+    /// If the key is already assigned, only the bytes of its value are replaced, along with
+    /// quotes if the value needs them and was not quoted. Otherwise, a new `key = "value"`
+    /// line is added to the end of the document.
+    ///
+    /// Preset values can not contain line breaks, quotes, or the start of a comment (`//` or
+    /// `#`), so these are rejected rather than written in a way that would change the meaning
+    /// of the document.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), DocumentEditError> {
+        if value.contains(['\n', '\r', '"', '#']) || value.contains("//") {
+            return Err(DocumentEditError::InvalidValue {
+                key: key.to_string(),
+                value: value.to_string(),
+            });
+        }
+        // unquoted values are trimmed when they are parsed.
+        let needs_quotes = value.is_empty() || value.contains(char::is_whitespace);
+
+        if let Some(index) = self.entries.iter().position(|entry| self.key(entry) == key) {
+            let span = self.entries[index].value_span();
+            let mut start = span.start;
+            if needs_quotes && !self.text[..start].ends_with('"') {
+                self.splice(span, &format!("\"{value}\""));
+                start += 1;
+            } else {
+                self.splice(span, value);
+            }
+            self.entries[index].value = start..start + value.len();
+            return Ok(());
+        }
+
+        if key.is_empty()
+            || key.contains(|c: char| c.is_whitespace() || matches!(c, '=' | '"' | '#'))
+            || key.contains("//")
+            || key.contains("/*")
+        {
+            return Err(DocumentEditError::InvalidKey(key.to_string()));
+        }
+
+        if !self.text.is_empty() && !self.text.ends_with('\n') {
+            self.text.push('\n');
+        }
+        let key_start = self.text.len();
+        self.text.push_str(key);
+        self.text.push_str(" = \"");
+        let value_start = self.text.len();
+        self.text.push_str(value);
+        self.text.push_str("\"\n");
+        self.entries.push(DocumentEntry {
+            key: key_start..key_start + key.len(),
+            value: value_start..value_start + value.len(),
+        });
+        Ok(())
+    }
+
+    /// Remove every assignment of the given key, along with the lines they are written on.
+    ///
+    /// Returns whether the key was assigned.
+    pub fn remove(&mut self, key: &str) -> bool {
+        let mut removed = false;
+        while let Some(index) = self.entries.iter().position(|entry| self.key(entry) == key) {
+            let entry = self.entries.remove(index);
+            let start = self.text[..entry.key.start]
+                .rfind('\n')
+                .map_or(0, |newline| newline + 1);
+            let end = self.text[entry.value.end..]
+                .find('\n')
+                .map_or(self.text.len(), |newline| entry.value.end + newline + 1);
+            self.splice(start..end, "");
+            removed = true;
+        }
+        removed
+    }
+}
+
+impl Display for PresetDocument {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{DocumentEditError, PresetDocument};
+
+    const PRESET: &str = r#"#reference "base.slangp"
+// scaling for the second pass
+scale_type2 = "source" // keep this
+scale2 = 2.0
+
+/* parameters */
+parameters = "ColorMod"
+ColorMod   =   "1.7"
+"#;
+
+    #[test]
+    fn round_trips_untouched_text() {
+        let document = PresetDocument::parse(PRESET).unwrap();
+        assert_eq!(PRESET, document.to_string());
+
+        let keys: Vec<&str> = document
+            .entries()
+            .iter()
+            .map(|entry| document.key(entry))
+            .collect();
+        assert_eq!(
+            vec![
+                "#reference",
+                "scale_type2",
+                "scale2",
+                "parameters",
+                "ColorMod"
+            ],
+            keys
+        );
+        assert_eq!(Some("base.slangp"), document.get("#reference"));
+        assert_eq!(Some("source"), document.get("scale_type2"));
+        assert_eq!(Some("2.0"), document.get("scale2"));
+    }
+
+    #[test]
+    fn edits_in_place() {
+        let mut document = PresetDocument::parse(PRESET).unwrap();
+        document.set("scale_type2", "viewport").unwrap();
+        document.set("ColorMod", "0.5").unwrap();
+        document.set("scale2", "1.0").unwrap();
+        document.set("filter_linear2", "true").unwrap();
+        assert!(document.remove("parameters"));
+        assert!(!document.remove("missing"));
+
+        assert_eq!(
+            r#"#reference "base.slangp"
+// scaling for the second pass
+scale_type2 = "viewport" // keep this
+scale2 = 1.0
+
+/* parameters */
+ColorMod   =   "0.5"
+filter_linear2 = "true"
+"#,
+            document.as_str()
+        );
+
+        let reparsed = PresetDocument::parse(document.to_string()).unwrap();
+        assert_eq!(document, reparsed);
+    }
+
+    #[test]
+    fn rejects_values_that_change_the_document() {
+        let mut document = PresetDocument::parse(PRESET).unwrap();
+        for value in ["a\nshaders = 99", "a\"b", "a // b", "a#b", "a\r"] {
+            assert_eq!(
+                Err(DocumentEditError::InvalidValue {
+                    key: "scale2".to_string(),
+                    value: value.to_string(),
+                }),
+                document.set("scale2", value)
+            );
+        }
+        for key in [
+            "",
+            "alias 0",
+            "shaders\nalias0",
+            "a=b",
+            "#shader",
+            "// alias0",
+        ] {
+            assert_eq!(
+                Err(DocumentEditError::InvalidKey(key.to_string())),
+                document.set(key, "1")
+            );
+        }
+        assert_eq!(PRESET, document.as_str());
+
+        document.set("scale2", "two words").unwrap();
+        document.set("ColorMod", "").unwrap();
+        document.set("alias0", " padded ").unwrap();
+        assert!(document.as_str().contains("scale2 = \"two words\"\n"));
+
+        let reparsed = PresetDocument::parse(document.to_string()).unwrap();
+        assert_eq!(document, reparsed);
+        assert_eq!(Some("two words"), reparsed.get("scale2"));
+        assert_eq!(Some(""), reparsed.get("ColorMod"));
+        assert_eq!(Some(" padded "), reparsed.get("alias0"));
+        assert_eq!(6, reparsed.entries().len());
+    }
+}
@@ -1,35 +1,94 @@
-use std::path::PathBuf;
+use librashader_common::map::ShortString;
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
 use thiserror::Error;
 
 /// Error type for preset parsing.
+///
+/// Errors that come from a preset file name the file in the reference tree they were found
+/// in. For presets parsed from a string, this is the path the preset was resolved against.
 #[derive(Error, Debug)]
 pub enum ParsePresetError {
     /// An error occurred when tokenizing the preset file.
-    #[error("shader preset lexing error")]
-    LexerError { offset: usize, row: u32, col: usize },
+    #[error("shader preset lexing error in {file:?} at {row}:{col}")]
+    LexerError {
+        file: PathBuf,
+        offset: usize,
+        row: u32,
+        col: usize,
+    },
     /// An error occurred when parsing the preset file.
-    #[error("shader preset parse error")]
+    #[error("shader preset parse error in {file:?} at {row}:{col}: expected {kind}")]
     ParserError {
+        file: PathBuf,
         offset: usize,
         row: u32,
         col: usize,
         kind: ParseErrorKind,
     },
     /// The scale type was invalid.
+    ///
+    /// Invalid scale types in a preset file are reported as a [`ParsePresetError::ParserError`].
     #[error("invalid scale type")]
     InvalidScaleType(String),
-    /// The preset reference depth exceeded 16.
-    #[error("exceeded maximum reference depth (16)")]
-    ExceededReferenceDepth,
+    /// The preprocessor definition was invalid.
+    ///
+    /// Invalid definitions in a preset file are reported as a [`ParsePresetError::ParserError`].
+    #[error("invalid preprocessor definition")]
+    InvalidDefine(String),
+    /// The preset reference depth exceeded 16 when following the `#reference`s of the given file.
+    #[error("exceeded maximum reference depth (16) in {0:?}")]
+    ExceededReferenceDepth(PathBuf),
+    /// A preset references itself, directly or through other presets.
+    ///
+    /// The chain of references starts at the root preset, and ends at the preset that was
+    /// referenced again.
+    #[error("shader preset reference cycle: {}", display_chain(.0))]
+    ReferenceCycle(Vec<PathBuf>),
     /// An absolute path could not be found to resolve the shader preset against.
-    #[error("shader presets must be resolved against an absolute path")]
-    RootPathWasNotAbsolute,
+    #[error("shader presets must be resolved against an absolute path, but got {0:?}")]
+    RootPathWasNotAbsolute(PathBuf),
+    /// A shader, texture or `#reference`d preset written in a preset file could not be found.
+    #[error("{path:?} referenced in {file:?} could not be found: {source}")]
+    MissingFile {
+        /// The preset file the path was written in.
+        file: PathBuf,
+        /// The path that could not be found.
+        path: PathBuf,
+        /// The error from the filesystem.
+        source: std::io::Error,
+    },
     /// An IO error occurred when reading the shader preset.
     #[error("io error on file {0:?}: {1}")]
     IOError(PathBuf, std::io::Error),
     /// The shader preset did not contain valid UTF-8 bytes.
-    #[error("expected utf8 bytes but got invalid utf8")]
-    Utf8Error(Vec<u8>),
+    #[error("expected utf8 bytes in {0:?} but got invalid utf8")]
+    Utf8Error(PathBuf, Vec<u8>),
+}
+
+fn display_chain(chain: &[PathBuf]) -> String {
+    chain
+        .iter()
+        .map(|path| path.display().to_string())
+        .collect::<Vec<_>>()
+        .join(" -> ")
+}
+
+impl ParsePresetError {
+    /// The file the error came from, if the error came from a file.
+    pub fn file(&self) -> Option<&Path> {
+        match self {
+            ParsePresetError::LexerError { file, .. }
+            | ParsePresetError::ParserError { file, .. }
+            | ParsePresetError::MissingFile { file, .. }
+            | ParsePresetError::ExceededReferenceDepth(file)
+            | ParsePresetError::RootPathWasNotAbsolute(file)
+            | ParsePresetError::IOError(file, _)
+            | ParsePresetError::Utf8Error(file, _) => Some(file),
+            ParsePresetError::ReferenceCycle(chain) => chain.last().map(PathBuf::as_path),
+            ParsePresetError::InvalidScaleType(_) | ParsePresetError::InvalidDefine(_) => None,
+        }
+    }
 }
 
 /// The kind of error that may occur in parsing.
@@ -45,4 +104,89 @@ pub enum ParseErrorKind {
     Float,
     /// Expected a boolean.
     Bool,
+    /// Expected a scale type.
+    ScaleType,
+    /// Expected a list of preprocessor definitions.
+    Define,
+}
+
+impl Display for ParseErrorKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseErrorKind::Index(key) => write!(f, "an indexed key `{key}N`"),
+            ParseErrorKind::Int => f.write_str("an integer"),
+            ParseErrorKind::UnsignedInt => f.write_str("an unsigned integer"),
+            ParseErrorKind::Float => f.write_str("a float"),
+            ParseErrorKind::Bool => f.write_str("a boolean"),
+            ParseErrorKind::ScaleType => f.write_str("a scale type"),
+            ParseErrorKind::Define => f.write_str("a list of preprocessor definitions"),
+        }
+    }
+}
+
+/// Error type for editing a [`PresetDocument`](crate::PresetDocument).
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum DocumentEditError {
+    /// The key can not be written to a preset, because it is empty, or contains whitespace,
+    /// `=`, a quote, or the start of a comment.
+    #[error("{0:?} is not a valid preset key")]
+    InvalidKey(String),
+    /// The value can not be written to a preset, because it contains a line break, a quote,
+    /// or the start of a comment.
+    #[error("value {value:?} of {key} can not be written to a preset")]
+    InvalidValue { key: String, value: String },
+}
+
+/// An axis of a shader pass framebuffer.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ScaleAxis {
+    /// The horizontal axis.
+    X,
+    /// The vertical axis.
+    Y,
+}
+
+/// An inconsistency found when validating a shader preset.
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum PresetValidationError {
+    /// There is no shader pass with the given index, although it is less than the shader count.
+    #[error("shader pass {0} is missing")]
+    MissingPass(i32),
+    /// The shader pass index is not less than the shader count, or is negative.
+    #[error("shader pass {0} is out of range for the shader count")]
+    PassOutOfRange(i32),
+    /// More than one shader pass has the given index.
+    #[error("shader pass {0} is defined more than once")]
+    DuplicatePass(i32),
+    /// The alias of the shader pass is empty, and will be ignored.
+    #[error("shader pass {0} has an empty alias")]
+    EmptyAlias(i32),
+    /// The alias is used by more than one shader pass.
+    #[error("alias {alias} is used by shader passes {first} and {second}")]
+    DuplicateAlias {
+        alias: ShortString,
+        first: i32,
+        second: i32,
+    },
+    /// The alias of the shader pass collides with the name of a built-in texture semantic.
+    #[error("alias {alias} of shader pass {pass} collides with a built-in semantic")]
+    ReservedAlias { pass: i32, alias: ShortString },
+    /// The name of a lookup texture is also the alias of a shader pass.
+    #[error("texture {texture} shadows the alias of shader pass {pass}")]
+    TextureShadowsAlias { texture: ShortString, pass: i32 },
+    /// Two presets being combined configure a lookup texture with the same name differently.
+    #[error("texture {0} is configured differently by both presets")]
+    ConflictingTexture(ShortString),
+    /// The scale factor is absolute, but the scale type is not, or the other way around.
+    #[error("scale factor of shader pass {pass} on axis {axis:?} does not match its scale type")]
+    ScaleFactorMismatch { pass: i32, axis: ScaleAxis },
+    /// The scale factor is not a positive, finite number.
+    #[error("scale factor of shader pass {pass} on axis {axis:?} must be positive")]
+    InvalidScaleFactor { pass: i32, axis: ScaleAxis },
+    /// Scale factors are set without a scale type, so they are treated as relative to the source.
+    #[error("shader pass {0} has a scale factor without a scale type")]
+    ScaleWithoutType(i32),
+    /// The parameter is set in the preset, but not declared by any shader.
+    #[error("parameter {0} is not declared by any shader")]
+    UndeclaredParameter(ShortString),
 }
@@ -0,0 +1,335 @@
+use crate::fs::{absolute_path, MemoryFileSystem, PresetFileSystem};
+use std::io::{Cursor, ErrorKind, Read, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+/// The container format of a shader preset bundle.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ArchiveFormat {
+    /// A zip archive, with entries compressed with deflate.
+    Zip,
+    /// An uncompressed tar archive.
+    Tar,
+}
+
+impl ArchiveFormat {
+    /// Guess the archive format from the extension of the path.
+    pub fn from_path(path: impl AsRef<Path>) -> Option<ArchiveFormat> {
+        let extension = path.as_ref().extension()?.to_str()?;
+        if extension.eq_ignore_ascii_case("zip") {
+            Some(ArchiveFormat::Zip)
+        } else if extension.eq_ignore_ascii_case("tar") {
+            Some(ArchiveFormat::Tar)
+        } else {
+            None
+        }
+    }
+}
+
+fn invalid_data(error: impl std::error::Error + Send + Sync + 'static) -> std::io::Error {
+    std::io::Error::new(ErrorKind::InvalidData, error)
+}
+
+impl MemoryFileSystem {
+    /// Read all files in a zip or tar archive into a new in-memory filesystem.
+    ///
+    /// Files are placed at the root of the filesystem, at their path within the archive.
+    pub fn from_archive(bytes: &[u8], format: ArchiveFormat) -> std::io::Result<Self> {
+        let mut fs = MemoryFileSystem::new();
+        match format {
+            ArchiveFormat::Zip => {
+                let mut archive = zip::ZipArchive::new(Cursor::new(bytes)).map_err(invalid_data)?;
+                for index in 0..archive.len() {
+                    let mut file = archive.by_index(index).map_err(invalid_data)?;
+                    if !file.is_file() {
+                        continue;
+                    }
+                    let Some(path) = file.enclosed_name() else {
+                        continue;
+                    };
+                    let mut contents = Vec::new();
+                    file.read_to_end(&mut contents)?;
+                    fs.insert(path, contents);
+                }
+            }
+            ArchiveFormat::Tar => {
+                let mut archive = tar::Archive::new(bytes);
+                for entry in archive.entries()? {
+                    let mut entry = entry?;
+                    if !entry.header().entry_type().is_file() {
+                        continue;
+                    }
+                    let path = entry.path()?.into_owned();
+                    let mut contents = Vec::new();
+                    entry.read_to_end(&mut contents)?;
+                    fs.insert(path, contents);
+                }
+            }
+        }
+        Ok(fs)
+    }
+
+    /// Write all files in the filesystem into a zip or tar archive.
+    ///
+    /// Entries are written in sorted order, without timestamps, so that packing the same
+    /// files always produces the same archive.
+    pub fn write_archive(
+        &self,
+        format: ArchiveFormat,
+        writer: impl Write + Seek,
+    ) -> std::io::Result<()> {
+        let mut files: Vec<(String, &[u8])> = self
+            .files
+            .iter()
+            .map(|(path, contents)| (archive_entry_name(path), contents.as_slice()))
+            .collect();
+        files.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        match format {
+            ArchiveFormat::Zip => {
+                let mut archive = zip::ZipWriter::new(writer);
+                let options = zip::write::SimpleFileOptions::default()
+                    .compression_method(zip::CompressionMethod::Deflated);
+                for (name, contents) in files {
+                    archive.start_file(name, options).map_err(invalid_data)?;
+                    archive.write_all(contents)?;
+                }
+                archive.finish().map_err(invalid_data)?;
+            }
+            ArchiveFormat::Tar => {
+                let mut archive = tar::Builder::new(writer);
+                for (name, contents) in files {
+                    let mut header = tar::Header::new_gnu();
+                    header.set_size(contents.len() as u64);
+                    header.set_mode(0o644);
+                    archive.append_data(&mut header, name, contents)?;
+                }
+                archive.into_inner()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Find the path of the root shader preset of a bundle.
+    ///
+    /// The root preset is the `.slangp` file closest to the root of the filesystem. If
+    /// there is no preset, or more than one preset is equally close to the root, the
+    /// bundle has no root preset.
+    pub fn root_preset(&self) -> Option<&Path> {
+        let mut presets = self
+            .paths()
+            .filter(|path| path.extension().is_some_and(|ext| ext == "slangp"));
+
+        let mut root = presets.next()?;
+        let mut ambiguous = false;
+        for preset in presets {
+            let depth = preset.components().count();
+            let root_depth = root.components().count();
+            if depth < root_depth {
+                root = preset;
+                ambiguous = false;
+            } else if depth == root_depth {
+                ambiguous = true;
+            }
+        }
+
+        (!ambiguous).then_some(root)
+    }
+}
+
+/// Get the name of the entry for a filesystem path in an archive.
+fn archive_entry_name(path: &Path) -> String {
+    path.components()
+        .filter_map(|c| match c {
+            std::path::Component::Normal(c) => Some(c.to_string_lossy()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// If the path is an archive, get the path of the root preset in the archive.
+///
+/// Any other path is returned as is.
+pub(crate) fn bundle_preset_path(
+    path: PathBuf,
+    fs: &dyn PresetFileSystem,
+) -> std::io::Result<PathBuf> {
+    let Some(format) = ArchiveFormat::from_path(&path) else {
+        return Ok(path);
+    };
+
+    // A directory can have an archive extension too.
+    let Ok(bytes) = fs.read(&path) else {
+        return Ok(path);
+    };
+
+    let archive = MemoryFileSystem::from_archive(&bytes, format)?;
+    let Some(root) = archive.root_preset() else {
+        return Err(std::io::Error::new(
+            ErrorKind::NotFound,
+            "the archive does not contain a single top-level shader preset",
+        ));
+    };
+
+    Ok(path.join(archive_entry_name(root)))
+}
+
+struct CachedArchive {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    files: Arc<MemoryFileSystem>,
+}
+
+// Loading a preset reads many files from the same archive one after another,
+// so the last archive opened is kept around instead of being read again for every file.
+static LAST_ARCHIVE: Mutex<Option<CachedArchive>> = Mutex::new(None);
+
+fn open_archive(path: &Path) -> std::io::Result<Arc<MemoryFileSystem>> {
+    let modified = std::fs::metadata(path)?.modified().ok();
+    let mut cache = LAST_ARCHIVE.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(cached) = cache.as_ref() {
+        if cached.path == path && cached.modified == modified {
+            return Ok(Arc::clone(&cached.files));
+        }
+    }
+
+    // NOPANIC: only paths with an archive format are opened.
+    let format = ArchiveFormat::from_path(path).unwrap();
+    let files = Arc::new(MemoryFileSystem::from_archive(
+        &std::fs::read(path)?,
+        format,
+    )?);
+    *cache = Some(CachedArchive {
+        path: path.to_path_buf(),
+        modified,
+        files: Arc::clone(&files),
+    });
+    Ok(files)
+}
+
+/// Split a path into the canonical path of the archive on disk it goes through, and the
+/// path of the entry within that archive.
+fn enclosing_archive(path: &Path) -> Option<(PathBuf, PathBuf)> {
+    let path = absolute_path(path);
+    let archive = path
+        .ancestors()
+        .skip(1)
+        .find(|ancestor| ArchiveFormat::from_path(ancestor).is_some() && ancestor.is_file())?;
+    let entry = path.strip_prefix(archive).ok()?.to_path_buf();
+    let archive = archive.canonicalize().ok()?;
+    Some((archive, entry))
+}
+
+/// Read a file from within an archive on disk, if the path goes through an archive.
+pub(super) fn read_enclosed(path: &Path) -> Option<std::io::Result<Vec<u8>>> {
+    let (archive, entry) = enclosing_archive(path)?;
+    Some(open_archive(&archive).and_then(|files| files.read(&entry)))
+}
+
+/// Canonicalize a path within an archive on disk, if the path goes through an archive.
+pub(super) fn canonicalize_enclosed(path: &Path) -> Option<std::io::Result<PathBuf>> {
+    let (archive, entry) = enclosing_archive(path)?;
+    Some(
+        open_archive(&archive)
+            .and_then(|files| files.canonicalize(&entry))
+            .map(|entry| archive.join(archive_entry_name(&entry))),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use crate::fs::{ArchiveFormat, DiskFileSystem, MemoryFileSystem, PresetFileSystem};
+    use crate::ShaderPreset;
+    use std::io::Cursor;
+    use std::path::{Path, PathBuf};
+
+    fn basic_bundle() -> MemoryFileSystem {
+        let mut fs = MemoryFileSystem::new();
+        fs.insert(
+            "presets/basic.slangp",
+            std::fs::read("../test/basic.slangp").unwrap(),
+        );
+        fs.insert(
+            "presets/basic.slang",
+            std::fs::read("../test/basic.slang").unwrap(),
+        );
+        fs.insert(
+            "textures/sf2.png",
+            std::fs::read("../test/sf2.png").unwrap(),
+        );
+        fs
+    }
+
+    #[test]
+    fn round_trips_archives() {
+        let bundle = basic_bundle();
+        for format in [ArchiveFormat::Zip, ArchiveFormat::Tar] {
+            let mut bytes = Cursor::new(Vec::new());
+            bundle.write_archive(format, &mut bytes).unwrap();
+
+            let read = MemoryFileSystem::from_archive(bytes.get_ref(), format).unwrap();
+            let mut paths: Vec<&Path> = read.paths().collect();
+            paths.sort();
+            assert_eq!(
+                vec![
+                    Path::new("/presets/basic.slang"),
+                    Path::new("/presets/basic.slangp"),
+                    Path::new("/textures/sf2.png")
+                ],
+                paths
+            );
+            assert_eq!(
+                bundle.read(Path::new("textures/sf2.png")).unwrap(),
+                read.read(Path::new("textures/sf2.png")).unwrap()
+            );
+            assert_eq!(Some(Path::new("/presets/basic.slangp")), read.root_preset());
+        }
+    }
+
+    #[test]
+    fn root_preset_must_be_unambiguous() {
+        let mut fs = MemoryFileSystem::new();
+        fs.insert("a.slangp", "");
+        fs.insert("b.slangp", "");
+        assert_eq!(None, fs.root_preset());
+
+        fs.insert("c/d.slangp", "");
+        assert_eq!(None, fs.root_preset());
+
+        fs.remove("a.slangp");
+        assert_eq!(Some(Path::new("/b.slangp")), fs.root_preset());
+    }
+
+    #[test]
+    fn parses_preset_from_archive_on_disk() {
+        let dir = std::env::temp_dir().join("librashader-presets-archive");
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let bundle = basic_bundle();
+        for (name, format) in [
+            ("basic.zip", ArchiveFormat::Zip),
+            ("basic.tar", ArchiveFormat::Tar),
+        ] {
+            let path = dir.join(name);
+            let mut bytes = Cursor::new(Vec::new());
+            bundle.write_archive(format, &mut bytes).unwrap();
+            std::fs::write(&path, bytes.into_inner()).unwrap();
+
+            let preset = ShaderPreset::try_parse(&path).unwrap();
+            let shader = &preset.shaders[0].name;
+            let expected: PathBuf = path.canonicalize().unwrap().join("presets/basic.slang");
+            assert_eq!(&expected, shader);
+            assert!(DiskFileSystem.exists(shader));
+            assert!(DiskFileSystem
+                .read(shader)
+                .unwrap()
+                .starts_with(b"#version"));
+
+            let texture = path.join("presets/../textures/sf2.png");
+            assert!(DiskFileSystem.exists(&texture));
+            assert!(!DiskFileSystem.exists(&path.join("textures/missing.png")));
+        }
+    }
+}
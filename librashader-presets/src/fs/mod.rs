@@ -0,0 +1,239 @@
+//! Filesystem abstraction for resolving shader presets.
+//!
+//! By default, shader presets and the files they reference are resolved against the
+//! host filesystem with [`DiskFileSystem`]. Presets that are embedded in an application
+//! or shipped inside an asset pack can instead be resolved entirely from memory with
+//! [`MemoryFileSystem`], or with a custom implementation of [`PresetFileSystem`].
+//!
+//! With the `archive` feature, [`DiskFileSystem`] also reads into zip and tar bundles
+//! transparently. A path such as `packs/crt.zip/presets/crt-royale.slangp` resolves to the
+//! `presets/crt-royale.slangp` entry of the `packs/crt.zip` archive, and parsing the
+//! archive itself as a preset parses the single top-level `.slangp` in the bundle.
+#[cfg(feature = "archive")]
+pub(crate) mod archive;
+
+#[cfg(feature = "archive")]
+pub use archive::ArchiveFormat;
+
+use librashader_common::map::FastHashMap;
+use std::io::ErrorKind;
+use std::path::{Component, Path, PathBuf};
+
+/// A filesystem that shader presets and their referenced files can be resolved from.
+pub trait PresetFileSystem {
+    /// Read the entire contents of the file at the given path.
+    fn read(&self, path: &Path) -> std::io::Result<Vec<u8>>;
+
+    /// Return the canonical, absolute form of the path.
+    ///
+    /// Returns an error if no file or directory exists at the path.
+    fn canonicalize(&self, path: &Path) -> std::io::Result<PathBuf>;
+
+    /// Return whether or not a file or directory exists at the path.
+    fn exists(&self, path: &Path) -> bool {
+        self.canonicalize(path).is_ok()
+    }
+}
+
+/// A [`PresetFileSystem`] backed by the host filesystem.
+///
+/// With the `archive` feature, paths that go through a zip or tar archive on disk are
+/// resolved against the entries of the archive.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct DiskFileSystem;
+
+impl PresetFileSystem for DiskFileSystem {
+    fn read(&self, path: &Path) -> std::io::Result<Vec<u8>> {
+        let result = std::fs::read(path);
+        #[cfg(feature = "archive")]
+        if result.is_err() {
+            if let Some(result) = archive::read_enclosed(path) {
+                return result;
+            }
+        }
+        result
+    }
+
+    fn canonicalize(&self, path: &Path) -> std::io::Result<PathBuf> {
+        let result = path.canonicalize();
+        #[cfg(feature = "archive")]
+        if result.is_err() {
+            if let Some(result) = archive::canonicalize_enclosed(path) {
+                return result;
+            }
+        }
+        result
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        if path.try_exists().unwrap_or(false) {
+            return true;
+        }
+        #[cfg(feature = "archive")]
+        if let Some(result) = archive::canonicalize_enclosed(path) {
+            return result.is_ok();
+        }
+        false
+    }
+}
+
+/// An in-memory [`PresetFileSystem`].
+///
+/// Relative paths are resolved against the root of the filesystem, so
+/// `shaders/crt.slangp` and `/shaders/crt.slangp` refer to the same file.
+/// Directories are implied by the paths of the files inserted.
+#[derive(Debug, Default, Clone)]
+pub struct MemoryFileSystem {
+    files: FastHashMap<PathBuf, Vec<u8>>,
+}
+
+impl MemoryFileSystem {
+    /// Create a new, empty in-memory filesystem.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Insert a file into the filesystem, replacing any file at the same path.
+    pub fn insert(&mut self, path: impl AsRef<Path>, contents: impl Into<Vec<u8>>) {
+        self.files
+            .insert(Self::resolve(path.as_ref()), contents.into());
+    }
+
+    /// Remove a file from the filesystem, returning its contents if it existed.
+    pub fn remove(&mut self, path: impl AsRef<Path>) -> Option<Vec<u8>> {
+        self.files.remove(&Self::resolve(path.as_ref()))
+    }
+
+    /// Iterate over the paths of all files in the filesystem.
+    pub fn paths(&self) -> impl Iterator<Item = &Path> {
+        self.files.keys().map(PathBuf::as_path)
+    }
+
+    fn resolve(path: &Path) -> PathBuf {
+        if path.has_root() {
+            normalize_path(path)
+        } else {
+            normalize_path(&Path::new("/").join(path))
+        }
+    }
+}
+
+impl PresetFileSystem for MemoryFileSystem {
+    fn read(&self, path: &Path) -> std::io::Result<Vec<u8>> {
+        self.files
+            .get(&Self::resolve(path))
+            .cloned()
+            .ok_or_else(|| ErrorKind::NotFound.into())
+    }
+
+    fn canonicalize(&self, path: &Path) -> std::io::Result<PathBuf> {
+        let path = Self::resolve(path);
+        if self.files.contains_key(&path) || self.files.keys().any(|f| f.starts_with(&path)) {
+            Ok(path)
+        } else {
+            Err(ErrorKind::NotFound.into())
+        }
+    }
+}
+
+/// Make a path absolute against the current directory, without touching the filesystem.
+pub fn absolute_path(path: &Path) -> PathBuf {
+    if path.has_root() {
+        return normalize_path(path);
+    }
+
+    match std::env::current_dir() {
+        Ok(cwd) => normalize_path(&cwd.join(path)),
+        Err(_) => normalize_path(path),
+    }
+}
+
+/// Lexically normalize a path, resolving `.` and `..` components.
+pub fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => match normalized.components().next_back() {
+                Some(Component::Normal(_)) => {
+                    normalized.pop();
+                }
+                // `..` at the root is still the root.
+                Some(Component::RootDir | Component::Prefix(_)) => {}
+                _ => normalized.push(component),
+            },
+            _ => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+/// Get the path of `path` relative to the directory `root`, separated with `/`.
+///
+/// If the path can not be made relative, the normalized path is returned as is.
+pub fn relative_path(root: &Path, path: &Path) -> PathBuf {
+    let path = normalize_path(path);
+    if path.is_relative() {
+        return path;
+    }
+
+    let root = normalize_path(root);
+    // Both paths must share the same prefix or root to be relative to each other.
+    if root.components().next() != path.components().next() {
+        return path;
+    }
+
+    let mut root_components = root.components().peekable();
+    let mut path_components = path.components().peekable();
+
+    while let (Some(a), Some(b)) = (root_components.peek(), path_components.peek()) {
+        if a != b {
+            break;
+        }
+        root_components.next();
+        path_components.next();
+    }
+
+    let mut relative: Vec<String> = root_components.map(|_| String::from("..")).collect();
+    relative.extend(path_components.map(|c| c.as_os_str().to_string_lossy().into_owned()));
+    PathBuf::from(relative.join("/"))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::fs::{relative_path, MemoryFileSystem, PresetFileSystem};
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn relative_paths() {
+        assert_eq!(
+            PathBuf::from("basic.slang"),
+            relative_path(Path::new("/shaders"), Path::new("/shaders/basic.slang"))
+        );
+        assert_eq!(
+            PathBuf::from("../crt/shaders/crt.slang"),
+            relative_path(
+                Path::new("/shaders/presets"),
+                Path::new("/shaders/presets/../../shaders/crt/./shaders/crt.slang")
+            )
+        );
+    }
+
+    #[test]
+    fn memory_fs_resolves_files_and_directories() {
+        let mut fs = MemoryFileSystem::new();
+        fs.insert("shaders/crt/crt.slang", "#version 450");
+
+        assert_eq!(
+            PathBuf::from("/shaders/crt/crt.slang"),
+            fs.canonicalize(Path::new("/shaders/presets/../crt/crt.slang"))
+                .unwrap()
+        );
+        assert!(fs.exists(Path::new("shaders/crt")));
+        assert!(!fs.exists(Path::new("shaders/crt/missing.slang")));
+        assert_eq!(
+            b"#version 450".as_slice(),
+            fs.read(Path::new("/shaders/crt/crt.slang")).unwrap()
+        );
+    }
+}
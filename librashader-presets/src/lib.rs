@@ -1,6 +1,6 @@
 //! Shader preset definition parsing for librashader.
 //!
-//! This crate contains facilities and types for parsing `.slangp` shader presets files.
+//! This crate contains facilities and types for parsing and writing `.slangp` shader presets files.
 //!
 //! Shader presets contain shader and texture parameters, and the order in which to apply a set of
 //! shaders in a filter chain. A librashader runtime takes a resulting [`ShaderPreset`]
@@ -8,11 +8,27 @@
 //!
 //! Re-exported as [`librashader::presets`](https://docs.rs/librashader/latest/librashader/presets/index.html).
 
+mod builder;
+mod builtin;
 pub mod context;
+mod dependency;
+mod diagnostic;
+mod document;
 mod error;
+pub mod fs;
 mod parse;
 mod preset;
+mod search;
+mod validate;
+mod write;
 
+pub use builder::ShaderPresetBuilder;
+pub use builtin::{BuiltinShader, BUILTIN_PREFIX};
 pub use context::WildcardContext;
+pub use dependency::*;
+pub use diagnostic::*;
+pub use document::{DocumentEntry, PresetDocument};
 pub use error::*;
+pub use fs::PresetFileSystem;
 pub use preset::*;
+pub use search::SearchRootSubstitution;
@@ -1,4 +1,4 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use nom_locate::LocatedSpan;
 use std::str;
@@ -8,13 +8,14 @@ mod token;
 mod value;
 
 pub(crate) type Span<'a> = LocatedSpan<&'a str>;
-pub(crate) use token::Token;
+pub(crate) use token::{do_lex, Token};
 
-use crate::context::{VideoDriver, WildcardContext};
+use crate::context::{VideoDriver, WildcardContext, WildcardResolution};
 use crate::error::ParsePresetError;
+use crate::fs::{DiskFileSystem, PresetFileSystem};
 use crate::parse::preset::resolve_values;
-use crate::parse::value::parse_preset;
-use crate::ShaderPreset;
+use crate::parse::value::{parse_preset, parse_preset_str, ParseOptions};
+use crate::{DependencyGraph, PresetDiagnostic, SearchRootSubstitution, ShaderPreset};
 
 pub(crate) fn remove_if<T>(values: &mut Vec<T>, f: impl FnMut(&T) -> bool) -> Option<T> {
     values.iter().position(f).map(|idx| values.remove(idx))
@@ -27,8 +28,13 @@ impl ShaderPreset {
     pub fn try_parse(path: impl AsRef<Path>) -> Result<ShaderPreset, ParsePresetError> {
         let mut context = WildcardContext::new();
         context.add_path_defaults(path.as_ref());
-        let values = parse_preset(path, WildcardContext::new())?;
-        Ok(resolve_values(values))
+        let parsed = parse_preset(
+            path,
+            WildcardContext::new(),
+            &DiskFileSystem,
+            ParseOptions::default(),
+        )?;
+        Ok(resolve_values(parsed.values))
     }
 
     /// Try to parse the shader preset at the given path.
@@ -41,8 +47,8 @@ impl ShaderPreset {
         let mut context = WildcardContext::new();
         context.add_path_defaults(path.as_ref());
         context.add_video_driver_defaults(driver);
-        let values = parse_preset(path, context)?;
-        Ok(resolve_values(values))
+        let parsed = parse_preset(path, context, &DiskFileSystem, ParseOptions::default())?;
+        Ok(resolve_values(parsed.values))
     }
 
     /// Try to parse the shader preset at the given path, with the exact provided context.
@@ -53,15 +59,120 @@ impl ShaderPreset {
         path: impl AsRef<Path>,
         context: WildcardContext,
     ) -> Result<ShaderPreset, ParsePresetError> {
-        let values = parse_preset(path, context)?;
-        Ok(resolve_values(values))
+        let parsed = parse_preset(path, context, &DiskFileSystem, ParseOptions::default())?;
+        Ok(resolve_values(parsed.values))
+    }
+
+    /// Try to parse the shader preset at the given path, resolving the preset and all files it
+    /// references from the provided filesystem, with the exact provided context.
+    pub fn try_parse_with_fs(
+        path: impl AsRef<Path>,
+        context: WildcardContext,
+        fs: &impl PresetFileSystem,
+    ) -> Result<ShaderPreset, ParsePresetError> {
+        let parsed = parse_preset(path, context, fs, ParseOptions::default())?;
+        Ok(resolve_values(parsed.values))
+    }
+
+    /// Try to parse the shader preset at the given path, with the exact provided context,
+    /// along with the graph of every file the preset depends on.
+    ///
+    /// The graph contains the `#reference` chain of the preset, and the shader sources and
+    /// textures that are used by the parsed preset. Shader sources and textures that were
+    /// overridden by another preset in the chain are not included. `#include` edges can only be
+    /// found by preprocessing the shader sources, and are not included.
+    pub fn try_parse_with_dependencies(
+        path: impl AsRef<Path>,
+        context: WildcardContext,
+        fs: &impl PresetFileSystem,
+    ) -> Result<(ShaderPreset, DependencyGraph), ParsePresetError> {
+        let mut parsed = parse_preset(path, context, fs, ParseOptions::default())?;
+        let preset = resolve_values(parsed.values);
+        parsed.dependencies.retain_used(&preset);
+        Ok((preset, parsed.dependencies))
+    }
+
+    /// Try to parse the shader preset at the given path with the exact provided context,
+    /// along with how wildcards in the paths of the preset were replaced.
+    ///
+    /// Wildcards are replaced in the paths of referenced presets, shaders and textures.
+    /// There is one [`WildcardResolution`] for each path in which a wildcard was replaced.
+    pub fn try_parse_with_wildcards(
+        path: impl AsRef<Path>,
+        context: WildcardContext,
+        fs: &impl PresetFileSystem,
+    ) -> Result<(ShaderPreset, Vec<WildcardResolution>), ParsePresetError> {
+        let parsed = parse_preset(path, context, fs, ParseOptions::default())?;
+        Ok((resolve_values(parsed.values), parsed.wildcards))
+    }
+
+    /// Try to parse the shader preset at the given path with the exact provided context,
+    /// looking for shaders and textures that can not be found relative to the preset in
+    /// the given search roots.
+    ///
+    /// Search roots are tried in order, such as the root of the `slang-shaders` repository,
+    /// then a user shader directory. Each path is tried under a search root as written, and
+    /// without its leading `.` and `..` components. Every path found this way is reported
+    /// as a [`SearchRootSubstitution`].
+    pub fn try_parse_with_search_roots(
+        path: impl AsRef<Path>,
+        context: WildcardContext,
+        search_roots: &[PathBuf],
+        fs: &impl PresetFileSystem,
+    ) -> Result<(ShaderPreset, Vec<SearchRootSubstitution>), ParsePresetError> {
+        let options = ParseOptions {
+            search_roots,
+            ..Default::default()
+        };
+        let parsed = parse_preset(path, context, fs, options)?;
+        Ok((resolve_values(parsed.values), parsed.substitutions))
+    }
+
+    /// Try to parse the shader preset at the given path leniently, with the exact provided
+    /// context.
+    ///
+    /// Instead of failing on the first missing file or invalid value, problems are collected as
+    /// diagnostics and parsing continues as far as possible. Missing `#reference`d presets
+    /// and invalid values are skipped, and missing shaders and textures are kept in the preset.
+    /// Unknown and duplicate keys, and values that were coerced to another value, are also
+    /// reported. Lexer errors and an unreadable root preset still fail to parse.
+    pub fn try_parse_lenient(
+        path: impl AsRef<Path>,
+        context: WildcardContext,
+        fs: &impl PresetFileSystem,
+    ) -> Result<(ShaderPreset, Vec<PresetDiagnostic>), ParsePresetError> {
+        let options = ParseOptions {
+            lenient: true,
+            ..Default::default()
+        };
+        let parsed = parse_preset(path, context, fs, options)?;
+        Ok((resolve_values(parsed.values), parsed.diagnostics))
+    }
+
+    /// Try to parse a shader preset from a string, with the exact provided context.
+    ///
+    /// `#reference` directives, shaders and textures in the preset are resolved relative to the
+    /// directory `root_path` in the provided filesystem.
+    pub fn try_parse_str(
+        contents: &str,
+        root_path: impl AsRef<Path>,
+        context: WildcardContext,
+        fs: &impl PresetFileSystem,
+    ) -> Result<ShaderPreset, ParsePresetError> {
+        let parsed = parse_preset_str(contents, root_path, context, fs, ParseOptions::default())?;
+        Ok(resolve_values(parsed.values))
     }
 }
 
 #[cfg(test)]
 mod test {
-    use crate::ShaderPreset;
-    use std::path::PathBuf;
+    use crate::context::{ContextItem, WildcardResolution};
+    use crate::fs::MemoryFileSystem;
+    use crate::{
+        Dependency, DependencyKind, ParsePresetError, PresetDiagnostic, PresetDiagnosticKind,
+        SearchRootSubstitution, ShaderDefine, ShaderPreset, WildcardContext,
+    };
+    use std::path::{Path, PathBuf};
 
     #[test]
     pub fn parse_preset() {
@@ -70,4 +181,430 @@ mod test {
         eprintln!("{basic:#?}");
         assert!(basic.is_ok());
     }
+
+    #[test]
+    pub fn parse_preset_from_memory() {
+        let mut fs = MemoryFileSystem::new();
+        fs.insert("shaders/stock.slang", "#version 450");
+        fs.insert("shaders/lut.png", []);
+        fs.insert(
+            "presets/base.slangp",
+            "shaders = 1\nshader0 = ../shaders/stock.slang\nscale_type0 = viewport\n",
+        );
+
+        let preset = ShaderPreset::try_parse_str(
+            "#reference \"base.slangp\"\ntextures = LUT\nLUT = ../shaders/lut.png\nColorMod = 0.5",
+            "presets",
+            WildcardContext::new(),
+            &fs,
+        )
+        .unwrap();
+
+        assert_eq!(1, preset.shaders.len());
+        assert_eq!(
+            PathBuf::from("/presets/../shaders/stock.slang"),
+            preset.shaders[0].name
+        );
+        assert_eq!(
+            PathBuf::from("/presets/../shaders/lut.png"),
+            preset.textures[0].path
+        );
+        assert_eq!(0.5, preset.parameters[0].value);
+
+        let missing =
+            ShaderPreset::try_parse_with_fs("presets/missing.slangp", WildcardContext::new(), &fs);
+        assert!(missing.is_err());
+    }
+
+    #[test]
+    pub fn parse_preset_dependencies() {
+        let mut fs = MemoryFileSystem::new();
+        fs.insert("shaders/stock.slang", "#version 450");
+        fs.insert("shaders/other.slang", "#version 450");
+        fs.insert("shaders/lut.png", []);
+        fs.insert(
+            "presets/base.slangp",
+            "shaders = 1\nshader0 = ../shaders/stock.slang\n",
+        );
+        fs.insert(
+            "presets/override.slangp",
+            "#reference \"base.slangp\"\ntextures = LUT\nLUT = ../shaders/lut.png\n",
+        );
+
+        let (preset, graph) = ShaderPreset::try_parse_with_dependencies(
+            "presets/override.slangp",
+            WildcardContext::new(),
+            &fs,
+        )
+        .unwrap();
+
+        assert_eq!(PathBuf::from("/presets/override.slangp"), graph.root);
+        assert_eq!(
+            vec![
+                Dependency {
+                    kind: DependencyKind::Reference,
+                    parent: PathBuf::from("/presets/override.slangp"),
+                    path: PathBuf::from("/presets/base.slangp"),
+                },
+                Dependency {
+                    kind: DependencyKind::Shader(0),
+                    parent: PathBuf::from("/presets/base.slangp"),
+                    path: PathBuf::from("/shaders/stock.slang"),
+                },
+                Dependency {
+                    kind: DependencyKind::Texture("LUT".into()),
+                    parent: PathBuf::from("/presets/override.slangp"),
+                    path: PathBuf::from("/shaders/lut.png"),
+                },
+            ],
+            graph.dependencies
+        );
+        assert_eq!(
+            PathBuf::from("/presets/../shaders/stock.slang"),
+            preset.shaders[0].name
+        );
+        assert_eq!(4, graph.files().count());
+        assert_eq!(
+            vec![0],
+            graph.affected_passes(Path::new("/shaders/stock.slang"))
+        );
+        assert!(graph
+            .affected_passes(Path::new("/shaders/other.slang"))
+            .is_empty());
+    }
+
+    #[test]
+    pub fn parse_preset_leniently() {
+        let mut fs = MemoryFileSystem::new();
+        fs.insert(
+            "shaders/stock.slang",
+            "#parameter ColorMod \"Color\" 1.0 0.0 2.0 0.1",
+        );
+        fs.insert(
+            "presets/base.slangp",
+            "#reference \"gone.slangp\"\nshaders = 2\nshader0 = ../shaders/stock.slang\nshader1 = ../shaders/missing.slang\n",
+        );
+        fs.insert(
+            "presets/override.slangp",
+            concat!(
+                "#reference \"base.slangp\"\n",
+                "filter_linear0 = maybe\n",
+                "foo = true\n",
+                "textures = LUT\n",
+                "LUT = ../shaders/lut.png\n",
+                "parameters = ColorMod\n",
+                "ColorMod = \"0'.5\"\n",
+                "ColorMod = 0.5\n",
+            ),
+        );
+
+        let strict =
+            ShaderPreset::try_parse_with_fs("presets/override.slangp", WildcardContext::new(), &fs);
+        assert!(strict.is_err());
+
+        let (preset, diagnostics) =
+            ShaderPreset::try_parse_lenient("presets/override.slangp", WildcardContext::new(), &fs)
+                .unwrap();
+        assert_eq!(2, preset.shaders.len());
+        assert_eq!(1, preset.textures.len());
+
+        let diagnostic = |file: &str, row, col, kind| PresetDiagnostic {
+            kind,
+            file: PathBuf::from(file),
+            row,
+            col,
+        };
+        let mut expected = vec![
+            diagnostic(
+                "/presets/base.slangp",
+                1,
+                13,
+                PresetDiagnosticKind::MissingFile(PathBuf::from("/presets/gone.slangp")),
+            ),
+            diagnostic(
+                "/presets/base.slangp",
+                4,
+                11,
+                PresetDiagnosticKind::MissingFile(PathBuf::from(
+                    "/presets/../shaders/missing.slang",
+                )),
+            ),
+            diagnostic(
+                "/presets/override.slangp",
+                2,
+                18,
+                PresetDiagnosticKind::InvalidValue {
+                    key: String::from("filter_linear0"),
+                    value: String::from("maybe"),
+                },
+            ),
+            diagnostic(
+                "/presets/override.slangp",
+                3,
+                1,
+                PresetDiagnosticKind::UnknownKey(String::from("foo")),
+            ),
+            diagnostic(
+                "/presets/override.slangp",
+                5,
+                7,
+                PresetDiagnosticKind::MissingFile(PathBuf::from("/presets/../shaders/lut.png")),
+            ),
+            diagnostic(
+                "/presets/override.slangp",
+                7,
+                13,
+                PresetDiagnosticKind::CoercedValue {
+                    key: String::from("ColorMod"),
+                    value: String::from("0'.5"),
+                    coerced: String::from("0"),
+                },
+            ),
+            diagnostic(
+                "/presets/override.slangp",
+                8,
+                1,
+                PresetDiagnosticKind::DuplicateKey(String::from("ColorMod")),
+            ),
+        ];
+        // diagnostics are reported in the order keys are processed, not the order they are written.
+        let mut diagnostics = diagnostics;
+        let position = |d: &PresetDiagnostic| (d.file.clone(), d.row, d.col);
+        diagnostics.sort_by_key(position);
+        expected.sort_by_key(position);
+        assert_eq!(expected, diagnostics);
+        assert_eq!(
+            "/presets/override.slangp:3:1: unknown key `foo`",
+            diagnostics[3].to_string()
+        );
+    }
+
+    #[test]
+    pub fn parse_preset_wildcards() {
+        let mut fs = MemoryFileSystem::new();
+        fs.insert("shaders/Snes9x/stock.slang", "#version 450");
+        fs.insert("shaders/stock.slang", "#version 450");
+        fs.insert("textures/$GAME$.png", []);
+        fs.insert(
+            "presets/wildcards.slangp",
+            concat!(
+                "shaders = 2\n",
+                "shader0 = ../shaders/$CORE$/stock.slang\n",
+                "shader1 = ../shaders/$CORE$/../stock.slang\n",
+                "textures = LUT\n",
+                "LUT = ../textures/$GAME$.png\n",
+            ),
+        );
+
+        let mut context = WildcardContext::new();
+        context.append_item(ContextItem::CoreName(String::from("Snes9x")));
+        context.append_item(ContextItem::GameName(String::from("missing")));
+
+        let (preset, wildcards) =
+            ShaderPreset::try_parse_with_wildcards("presets/wildcards.slangp", context, &fs)
+                .unwrap();
+        assert_eq!(
+            PathBuf::from("/presets/../shaders/Snes9x/stock.slang"),
+            preset.shaders[0].name
+        );
+        assert_eq!(
+            PathBuf::from("/presets/../textures/$GAME$.png"),
+            preset.textures[0].path
+        );
+
+        let file = Some(PathBuf::from("/presets/wildcards.slangp"));
+        assert_eq!(3, wildcards.len());
+        assert_eq!(
+            WildcardResolution {
+                file: file.clone(),
+                original: PathBuf::from("/presets/../shaders/$CORE$/stock.slang"),
+                wildcards: vec![String::from("CORE")],
+                tried: vec![PathBuf::from("/presets/../shaders/Snes9x/stock.slang")],
+                resolved: PathBuf::from("/presets/../shaders/Snes9x/stock.slang"),
+            },
+            wildcards[0]
+        );
+        assert_eq!(
+            WildcardResolution {
+                file,
+                original: PathBuf::from("/presets/../textures/$GAME$.png"),
+                wildcards: vec![String::from("GAME")],
+                tried: vec![
+                    PathBuf::from("/presets/../textures/missing.png"),
+                    PathBuf::from("/presets/../textures/$GAME$.png"),
+                ],
+                resolved: PathBuf::from("/presets/../textures/$GAME$.png"),
+            },
+            wildcards[2]
+        );
+    }
+
+    #[test]
+    pub fn parse_preset_search_roots() {
+        let mut fs = MemoryFileSystem::new();
+        fs.insert("slang-shaders/stock.slang", "#version 450");
+        fs.insert("user/textures/lut.png", []);
+        fs.insert("presets/crt/local.slang", "#version 450");
+        fs.insert(
+            "presets/crt/moved.slangp",
+            concat!(
+                "shaders = 2\n",
+                "shader0 = ../stock.slang\n",
+                "shader1 = local.slang\n",
+                "textures = LUT\n",
+                "LUT = textures/lut.png\n",
+            ),
+        );
+
+        let roots = [PathBuf::from("/slang-shaders"), PathBuf::from("/user")];
+        let (preset, substitutions) = ShaderPreset::try_parse_with_search_roots(
+            "presets/crt/moved.slangp",
+            WildcardContext::new(),
+            &roots,
+            &fs,
+        )
+        .unwrap();
+
+        assert_eq!(
+            PathBuf::from("/slang-shaders/stock.slang"),
+            preset.shaders[0].name
+        );
+        assert_eq!(
+            PathBuf::from("/presets/crt/local.slang"),
+            preset.shaders[1].name
+        );
+        assert_eq!(
+            PathBuf::from("/user/textures/lut.png"),
+            preset.textures[0].path
+        );
+        assert_eq!(
+            vec![
+                SearchRootSubstitution {
+                    file: PathBuf::from("/presets/crt/moved.slangp"),
+                    original: PathBuf::from("/presets/crt/../stock.slang"),
+                    root: PathBuf::from("/slang-shaders"),
+                    resolved: PathBuf::from("/slang-shaders/stock.slang"),
+                },
+                SearchRootSubstitution {
+                    file: PathBuf::from("/presets/crt/moved.slangp"),
+                    original: PathBuf::from("/presets/crt/textures/lut.png"),
+                    root: PathBuf::from("/user"),
+                    resolved: PathBuf::from("/user/textures/lut.png"),
+                },
+            ],
+            substitutions
+        );
+
+        let missing = ShaderPreset::try_parse_with_fs(
+            "presets/crt/moved.slangp",
+            WildcardContext::new(),
+            &fs,
+        );
+        assert!(missing.is_err());
+    }
+
+    #[test]
+    pub fn parse_preset_reference_cycle() {
+        let mut fs = MemoryFileSystem::new();
+        fs.insert("presets/a.slangp", "#reference \"b.slangp\"\n");
+        fs.insert("presets/b.slangp", "#reference \"a.slangp\"\n");
+
+        let err = ShaderPreset::try_parse_with_fs("presets/a.slangp", WildcardContext::new(), &fs)
+            .unwrap_err();
+        let ParsePresetError::ReferenceCycle(chain) = &err else {
+            panic!("expected a reference cycle, got {err:?}");
+        };
+        assert_eq!(
+            &vec![
+                PathBuf::from("/presets/a.slangp"),
+                PathBuf::from("/presets/b.slangp"),
+                PathBuf::from("/presets/a.slangp"),
+            ],
+            chain
+        );
+        assert_eq!(
+            "shader preset reference cycle: /presets/a.slangp -> /presets/b.slangp -> /presets/a.slangp",
+            err.to_string()
+        );
+    }
+
+    #[test]
+    pub fn parse_preset_error_file() {
+        let mut fs = MemoryFileSystem::new();
+        fs.insert(
+            "presets/base.slangp",
+            "shaders = 1\nshader0 = stock.slang\nshaders = many\n",
+        );
+        fs.insert("presets/stock.slang", "#version 450");
+        fs.insert("presets/child.slangp", "#reference \"base.slangp\"\n");
+
+        let err =
+            ShaderPreset::try_parse_with_fs("presets/child.slangp", WildcardContext::new(), &fs)
+                .unwrap_err();
+        assert!(matches!(&err, ParsePresetError::ParserError { row: 3, .. }));
+        assert_eq!(Some(Path::new("/presets/base.slangp")), err.file());
+        fs.insert(
+            "presets/base.slangp",
+            "shaders = 1\nshader0 = missing.slang\n",
+        );
+        let err =
+            ShaderPreset::try_parse_with_fs("presets/child.slangp", WildcardContext::new(), &fs)
+                .unwrap_err();
+        let ParsePresetError::MissingFile { path, .. } = &err else {
+            panic!("expected a missing file, got {err:?}");
+        };
+        assert_eq!(Path::new("/presets/missing.slang"), path);
+        assert_eq!(Some(Path::new("/presets/base.slangp")), err.file());
+
+        fs.insert("presets/child.slangp", "#reference \"gone.slangp\"\n");
+        let err =
+            ShaderPreset::try_parse_with_fs("presets/child.slangp", WildcardContext::new(), &fs)
+                .unwrap_err();
+        assert!(matches!(&err, ParsePresetError::MissingFile { path, .. }
+            if path == Path::new("/presets/gone.slangp")));
+        assert_eq!(Some(Path::new("/presets/child.slangp")), err.file());
+    }
+
+    #[test]
+    pub fn parse_preset_defines() {
+        let mut fs = MemoryFileSystem::new();
+        fs.insert("presets/stock.slang", "#version 450");
+        fs.insert(
+            "presets/defines.slangp",
+            concat!(
+                "shaders = 2\n",
+                "shader0 = stock.slang\n",
+                "defines0 = \"USE_FAST_PATH=1; MODE = fast ;LIBRASHADER_TARGET_WGSL;\"\n",
+                "shader1 = stock.slang\n",
+            ),
+        );
+
+        let preset =
+            ShaderPreset::try_parse_with_fs("presets/defines.slangp", WildcardContext::new(), &fs)
+                .unwrap();
+        assert_eq!(
+            vec![
+                ShaderDefine::new("USE_FAST_PATH", "1"),
+                ShaderDefine::new("MODE", "fast"),
+                ShaderDefine::new("LIBRASHADER_TARGET_WGSL", "1"),
+            ],
+            preset.shaders[0].defines
+        );
+        assert!(preset.shaders[1].defines.is_empty());
+
+        let written = preset.to_slangp_string("/presets");
+        assert!(
+            written.contains("defines0 = \"USE_FAST_PATH=1;MODE=fast;LIBRASHADER_TARGET_WGSL=1\"")
+        );
+        assert!(!written.contains("defines1"));
+
+        fs.insert(
+            "presets/invalid.slangp",
+            "shaders = 1\nshader0 = stock.slang\ndefines0 = \"1ST=1\"\n",
+        );
+        let err =
+            ShaderPreset::try_parse_with_fs("presets/invalid.slangp", WildcardContext::new(), &fs)
+                .unwrap_err();
+        assert!(matches!(err, ParsePresetError::ParserError { row: 3, .. }));
+    }
 }
@@ -162,6 +162,13 @@ pub fn resolve_values(mut values: Vec<Value>) -> ShaderPreset {
                         _ => None,
                     })
                     .unwrap_or(false),
+                defines: shader_values
+                    .iter()
+                    .find_map(|f| match f {
+                        Value::Defines(_, value) => Some(value.clone()),
+                        _ => None,
+                    })
+                    .unwrap_or_default(),
                 scaling: Scale2D {
                     valid: scale_valid,
                     x: Scaling {
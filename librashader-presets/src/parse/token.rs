@@ -1,9 +1,10 @@
 use crate::error::ParsePresetError;
 use crate::parse::Span;
 use nom::branch::alt;
-use nom::bytes::complete::{is_not, take_until};
+use nom::bytes::complete::{take_till, take_until};
 use nom::character::complete::{char, line_ending, multispace1, not_line_ending};
 use std::ops::RangeFrom;
+use std::path::Path;
 
 use nom::combinator::{eof, map_res, value};
 use nom::error::{ErrorKind, ParseError};
@@ -69,7 +70,8 @@ where
 
 fn extract_from_quotes(input: Span) -> IResult<Span, Span> {
     // Allow unbalanced quotes because some presets just leave an open quote.
-    let (input, between) = delimited(char('"'), is_not("\""), unbalanced_quote)(input)?;
+    // Empty quotes (i.e. `alias0 = ""`) are an empty value.
+    let (input, between) = delimited(char('"'), take_till(|c| c == '"'), unbalanced_quote)(input)?;
     let (input, _) = opt_whitespace(input)?;
     let (input, _) = eof(input)?;
     Ok((input, between))
@@ -148,19 +150,21 @@ fn parse_tokens(mut span: Span) -> IResult<Span, Vec<Token>> {
     Ok((span, values))
 }
 
-pub fn do_lex(input: &str) -> Result<Vec<Token>, ParsePresetError> {
+pub fn do_lex<'a>(input: &'a str, file: &Path) -> Result<Vec<Token<'a>>, ParsePresetError> {
     let span = Span::new(input.trim_end());
     let (_, tokens) = parse_tokens(span).map_err(|e| match e {
         nom::Err::Error(e) | nom::Err::Failure(e) => {
             let input: Span = e.input;
             println!("{:?}", input);
             ParsePresetError::LexerError {
+                file: file.to_path_buf(),
                 offset: input.location_offset(),
                 row: input.location_line(),
                 col: input.get_column(),
             }
         }
         _ => ParsePresetError::LexerError {
+            file: file.to_path_buf(),
             offset: 0,
             row: 0,
             col: 0,
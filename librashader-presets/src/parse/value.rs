@@ -1,6 +1,6 @@
 use crate::error::{ParseErrorKind, ParsePresetError};
 use crate::parse::{remove_if, Span, Token};
-use crate::{ScaleFactor, ScaleType};
+use crate::{ScaleFactor, ScaleType, ShaderDefine};
 use nom::bytes::complete::tag;
 use nom::character::complete::digit1;
 use nom::combinator::{eof, map_res};
@@ -12,12 +12,15 @@ use num_traits::cast::ToPrimitive;
 use crate::parse::token::do_lex;
 use librashader_common::map::{FastHashMap, ShortString};
 use librashader_common::{FilterMode, WrapMode};
-use std::fs::File;
-use std::io::Read;
+use std::ffi::OsStr;
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
-use crate::context::{apply_context, WildcardContext};
+use crate::builtin::{is_builtin_path, BuiltinShader, BUILTIN_PREFIX};
+use crate::context::{apply_context, WildcardContext, WildcardResolution};
+use crate::fs::{normalize_path, PresetFileSystem};
+use crate::search::{search_root_candidates, SearchRootSubstitution};
+use crate::{Dependency, DependencyGraph, DependencyKind, PresetDiagnostic, PresetDiagnosticKind};
 use vec_extract_if_polyfill::MakeExtractIf;
 
 #[derive(Debug)]
@@ -38,6 +41,7 @@ pub enum Value {
     SrgbFramebuffer(i32, bool),
     MipmapInput(i32, bool),
     Alias(i32, ShortString),
+    Defines(i32, Vec<ShaderDefine>),
     Parameter(ShortString, f32),
     Texture {
         name: ShortString,
@@ -65,82 +69,92 @@ impl Value {
             Value::SrgbFramebuffer(i, _) => Some(*i),
             Value::MipmapInput(i, _) => Some(*i),
             Value::Alias(i, _) => Some(*i),
+            Value::Defines(i, _) => Some(*i),
             _ => None,
         }
     }
 }
 
-fn from_int(input: Span) -> Result<i32, ParsePresetError> {
+/// A value that could not be parsed, before the file it was read from is known.
+struct ValueError {
+    offset: usize,
+    row: u32,
+    col: usize,
+    kind: ParseErrorKind,
+}
+
+impl ValueError {
+    fn new(input: Span, kind: ParseErrorKind) -> Self {
+        ValueError {
+            offset: input.location_offset(),
+            row: input.location_line(),
+            col: input.get_column(),
+            kind,
+        }
+    }
+
+    /// Report the error against the preset file the value was read from.
+    fn in_file(self, file: &Path) -> ParsePresetError {
+        ParsePresetError::ParserError {
+            file: file.to_path_buf(),
+            offset: self.offset,
+            row: self.row,
+            col: self.col,
+            kind: self.kind,
+        }
+    }
+}
+
+fn from_int(input: Span) -> Result<i32, ValueError> {
     // Presets like to commit ✨CRIMES✨ and end their lines with a ";".
     // It's too hard to put this in the lexer because we can't tell between
     // semicolon crimes or a valid param/texture name listing.
     let to_parse = input.trim().trim_end_matches(";");
     i32::from_str(to_parse)
-        .map_err(|_| ParsePresetError::ParserError {
-            offset: input.location_offset(),
-            row: input.location_line(),
-            col: input.get_column(),
-            kind: ParseErrorKind::Int,
-        })
-        .or_else(|e| {
-            // An even more egregious ✨CRIME✨ is using a float as a shader index.
-            let result = f32::from_str(to_parse).map_err(|_| e)?;
-            let result = result
-                .trunc()
-                .to_i32()
-                .ok_or(ParsePresetError::ParserError {
-                    offset: input.location_offset(),
-                    row: input.location_line(),
-                    col: input.get_column(),
-                    kind: ParseErrorKind::Int,
-                })?;
-            Ok(result)
-        })
+        .ok()
+        // An even more egregious ✨CRIME✨ is using a float as a shader index.
+        .or_else(|| f32::from_str(to_parse).ok()?.trunc().to_i32())
+        .ok_or_else(|| ValueError::new(input, ParseErrorKind::Int))
 }
 
-fn from_ul(input: Span) -> Result<u32, ParsePresetError> {
+fn from_ul(input: Span) -> Result<u32, ValueError> {
     // Presets like to commit ✨CRIMES✨ and end their lines with a ";".
     // It's too hard to put this in the lexer because we can't tell between
     // semicolon crimes or a valid param/texture name listing.
-    u32::from_str(input.trim().trim_end_matches(";")).map_err(|_| ParsePresetError::ParserError {
-        offset: input.location_offset(),
-        row: input.location_line(),
-        col: input.get_column(),
-        kind: ParseErrorKind::UnsignedInt,
-    })
+    u32::from_str(input.trim().trim_end_matches(";"))
+        .map_err(|_| ValueError::new(input, ParseErrorKind::UnsignedInt))
 }
 
-fn from_float(input: Span) -> Result<f32, ParsePresetError> {
+fn from_float(input: Span) -> Result<f32, ValueError> {
     // Presets like to commit ✨CRIMES✨ and end their lines with a ";".
     // It's too hard to put this in the lexer because we can't tell between
     // semicolon crimes or a valid param/texture name listing.
-    f32::from_str(input.trim().trim_end_matches(";")).map_err(|_| ParsePresetError::ParserError {
-        offset: input.location_offset(),
-        row: input.location_line(),
-        col: input.get_column(),
-        kind: ParseErrorKind::Float,
-    })
+    f32::from_str(input.trim().trim_end_matches(";"))
+        .map_err(|_| ValueError::new(input, ParseErrorKind::Float))
 }
 
-fn from_bool(input: Span) -> Result<bool, ParsePresetError> {
+fn from_bool(input: Span) -> Result<bool, ValueError> {
     if let Ok(i) = i32::from_str(input.trim()) {
         return match i {
             1 => Ok(true),
             0 => Ok(false),
-            _ => Err(ParsePresetError::ParserError {
-                offset: input.location_offset(),
-                row: input.location_line(),
-                col: input.get_column(),
-                kind: ParseErrorKind::Bool,
-            }),
+            _ => Err(ValueError::new(input, ParseErrorKind::Bool)),
         };
     }
-    bool::from_str(input.trim()).map_err(|_| ParsePresetError::ParserError {
-        offset: input.location_offset(),
-        row: input.location_line(),
-        col: input.get_column(),
-        kind: ParseErrorKind::Bool,
-    })
+    bool::from_str(input.trim()).map_err(|_| ValueError::new(input, ParseErrorKind::Bool))
+}
+
+fn from_scale_type(input: Span) -> Result<ScaleType, ValueError> {
+    ScaleType::from_str(input.trim()).map_err(|_| ValueError::new(input, ParseErrorKind::ScaleType))
+}
+
+fn from_defines(input: Span) -> Result<Vec<ShaderDefine>, ValueError> {
+    input
+        .split(';')
+        .filter(|define| !define.trim().is_empty())
+        .map(ShaderDefine::from_str)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| ValueError::new(input, ParseErrorKind::Define))
 }
 
 fn parse_indexed_key<'a>(key: &'static str, input: Span<'a>) -> IResult<Span<'a>, i32> {
@@ -152,127 +166,497 @@ fn parse_indexed_key<'a>(key: &'static str, input: Span<'a>) -> IResult<Span<'a>
 
 pub const SHADER_MAX_REFERENCE_DEPTH: usize = 16;
 
+/// A `#reference` directive in a preset.
+struct PresetReference {
+    path: PathBuf,
+    row: u32,
+    col: usize,
+}
+
+impl PresetReference {
+    fn extract(tokens: &mut Vec<Token>) -> Vec<PresetReference> {
+        MakeExtractIf::extract_if(tokens, |token| *token.key.fragment() == "#reference")
+            .map(|token| PresetReference {
+                path: PathBuf::from(*token.value.fragment()),
+                row: token.value.location_line(),
+                col: token.value.get_column(),
+            })
+            .collect()
+    }
+}
+
+/// A preset file that was read while resolving references.
+struct ReferencedPreset {
+    /// The directory the preset is in.
+    dir: PathBuf,
+    /// The path of the preset.
+    file: PathBuf,
+    /// The contents of the preset.
+    contents: String,
+}
+
+/// The preset file a token was read from.
+#[derive(Copy, Clone)]
+struct TokenSource<'a> {
+    dir: &'a Path,
+    file: &'a Path,
+}
+
+/// The result of parsing a preset and every preset it references.
+pub(crate) struct ParsedPreset {
+    pub values: Vec<Value>,
+    pub dependencies: DependencyGraph,
+    pub diagnostics: Vec<PresetDiagnostic>,
+    pub wildcards: Vec<WildcardResolution>,
+    pub substitutions: Vec<SearchRootSubstitution>,
+}
+
+/// Options for how a preset is parsed.
+#[derive(Debug, Default)]
+pub(crate) struct ParseOptions<'a> {
+    /// Whether to collect diagnostics instead of failing where possible.
+    pub lenient: bool,
+    /// Directories to look for shaders and textures in, if they can not be found relative
+    /// to the preset.
+    pub search_roots: &'a [PathBuf],
+}
+
+/// State shared across every file read while parsing a preset.
+struct ParseState<'a> {
+    context: FastHashMap<String, String>,
+    fs: &'a dyn PresetFileSystem,
+    dependencies: Vec<Dependency>,
+    wildcards: Vec<WildcardResolution>,
+    search_roots: &'a [PathBuf],
+    substitutions: Vec<SearchRootSubstitution>,
+    /// Diagnostics collected when parsing leniently. Parsing is strict when this is `None`.
+    diagnostics: Option<Vec<PresetDiagnostic>>,
+}
+
+impl ParseState<'_> {
+    /// Replace the wildcards in a path written in the given preset file.
+    fn apply_context(&mut self, path: &mut PathBuf, file: Option<&Path>) {
+        if let Some(resolution) = apply_context(path, file, &self.context, self.fs) {
+            self.wildcards.push(resolution);
+        }
+    }
+
+    fn diagnose(&mut self, file: &Path, row: u32, col: usize, kind: PresetDiagnosticKind) {
+        if let Some(diagnostics) = &mut self.diagnostics {
+            diagnostics.push(PresetDiagnostic {
+                kind,
+                file: file.to_path_buf(),
+                row,
+                col,
+            })
+        }
+    }
+
+    fn diagnose_at(&mut self, file: &Path, span: Span, kind: PresetDiagnosticKind) {
+        self.diagnose(file, span.location_line(), span.get_column(), kind)
+    }
+
+    /// Look for a shader or texture path that does not exist in the fallback search roots.
+    fn search_roots(&mut self, path: &mut PathBuf, written: &str, file: &Path) {
+        if self.search_roots.is_empty() || self.fs.canonicalize(path).is_ok() {
+            return;
+        }
+
+        for root in self.search_roots {
+            let found = search_root_candidates(root, Path::new(written))
+                .into_iter()
+                .find(|candidate| self.fs.exists(candidate));
+            if let Some(found) = found {
+                self.substitutions.push(SearchRootSubstitution {
+                    file: file.to_path_buf(),
+                    original: path.clone(),
+                    root: root.clone(),
+                    resolved: found.clone(),
+                });
+                *path = found;
+                return;
+            }
+        }
+    }
+
+    /// Get the built-in shader to use for a `stock.slang` shader path that does not exist.
+    ///
+    /// Only `stock.slang` is replaced, since every copy of it passes the source through
+    /// unchanged. Other shaders with the name of a built-in shader, such as `gamma.slang`,
+    /// may not behave the same as the built-in shader. Every replacement is reported.
+    fn builtin_fallback(
+        &mut self,
+        path: &Path,
+        source: TokenSource,
+        span: Span,
+    ) -> Option<BuiltinShader> {
+        let builtin = BuiltinShader::Stock;
+        if path.file_name() != Some(OsStr::new(builtin.file_name()))
+            || self.fs.canonicalize(path).is_ok()
+        {
+            return None;
+        }
+
+        self.substitutions.push(SearchRootSubstitution {
+            file: source.file.to_path_buf(),
+            original: path.to_path_buf(),
+            root: PathBuf::from(BUILTIN_PREFIX),
+            resolved: builtin.path(),
+        });
+        self.diagnose_at(
+            source.file,
+            span,
+            PresetDiagnosticKind::BuiltinFallback {
+                path: path.to_path_buf(),
+                builtin,
+            },
+        );
+        Some(builtin)
+    }
+
+    /// Check that a file referred to by the preset exists.
+    ///
+    /// When parsing leniently, a missing file is reported instead of failing.
+    fn check_exists(
+        &mut self,
+        path: &Path,
+        source: TokenSource,
+        span: Span,
+    ) -> Result<(), ParsePresetError> {
+        match self.fs.canonicalize(path) {
+            Ok(_) => Ok(()),
+            Err(_) if self.diagnostics.is_some() => {
+                self.diagnose_at(
+                    source.file,
+                    span,
+                    PresetDiagnosticKind::MissingFile(path.to_path_buf()),
+                );
+                Ok(())
+            }
+            Err(e) => Err(ParsePresetError::MissingFile {
+                file: source.file.to_path_buf(),
+                path: path.to_path_buf(),
+                source: e,
+            }),
+        }
+    }
+
+    /// Recover from a value that could not be parsed.
+    ///
+    /// When parsing leniently, the invalid value is reported and ignored instead of failing.
+    fn recover<T>(
+        &mut self,
+        result: Result<T, ValueError>,
+        source: TokenSource,
+        token: &Token,
+    ) -> Result<Option<T>, ParsePresetError> {
+        match result {
+            Ok(value) => Ok(Some(value)),
+            Err(_) if self.diagnostics.is_some() => {
+                self.diagnose_at(
+                    source.file,
+                    token.value,
+                    PresetDiagnosticKind::InvalidValue {
+                        key: token.key.fragment().to_string(),
+                        value: token.value.fragment().to_string(),
+                    },
+                );
+                Ok(None)
+            }
+            Err(e) => Err(e.in_file(source.file)),
+        }
+    }
+
+    fn parse_wrap_mode(&mut self, source: TokenSource, token: &Token) -> WrapMode {
+        let value = *token.value.fragment();
+        if !matches!(
+            value,
+            "clamp_to_border" | "clamp_to_edge" | "repeat" | "mirrored_repeat"
+        ) {
+            self.diagnose_at(
+                source.file,
+                token.value,
+                PresetDiagnosticKind::CoercedValue {
+                    key: token.key.fragment().to_string(),
+                    value: value.to_string(),
+                    coerced: String::from("clamp_to_border"),
+                },
+            );
+        }
+        // NOPANIC: infallible
+        WrapMode::from_str(value).unwrap()
+    }
+
+    fn parse_filter_mode(&mut self, source: TokenSource, token: &Token) -> FilterMode {
+        let value = *token.value.fragment();
+        if !matches!(value, "linear" | "nearest") {
+            self.diagnose_at(
+                source.file,
+                token.value,
+                PresetDiagnosticKind::CoercedValue {
+                    key: token.key.fragment().to_string(),
+                    value: value.to_string(),
+                    coerced: String::from("nearest"),
+                },
+            );
+        }
+        // NOPANIC: infallible
+        FilterMode::from_str(value).unwrap()
+    }
+
+    fn check_duplicate_keys(&mut self, source: TokenSource, tokens: &[Token]) {
+        if self.diagnostics.is_none() {
+            return;
+        }
+
+        let mut seen: Vec<&str> = Vec::new();
+        for token in tokens {
+            let key = *token.key.fragment();
+            if key == "#reference" {
+                continue;
+            }
+            if seen.contains(&key) {
+                self.diagnose_at(
+                    source.file,
+                    token.key,
+                    PresetDiagnosticKind::DuplicateKey(key.to_string()),
+                );
+            } else {
+                seen.push(key);
+            }
+        }
+    }
+}
+
 // prereq: root_path must be contextualized
 fn load_child_reference_strings(
-    root_references: Vec<PathBuf>,
-    root_path: impl AsRef<Path>,
-    context: &FastHashMap<String, String>,
-) -> Result<Vec<(PathBuf, String)>, ParsePresetError> {
-    let root_path = root_path.as_ref();
-
+    root_references: Vec<PresetReference>,
+    root_path: &Path,
+    root_file: &Path,
+    state: &mut ParseState,
+) -> Result<Vec<ReferencedPreset>, ParsePresetError> {
     let mut reference_depth = 0;
-    let mut reference_strings: VecDeque<(PathBuf, String)> = VecDeque::new();
-    let root_references = vec![(root_path.to_path_buf(), root_references)];
+    let mut reference_strings: VecDeque<ReferencedPreset> = VecDeque::new();
+    let root_references = vec![(
+        root_path.to_path_buf(),
+        root_file.to_path_buf(),
+        vec![root_file.to_path_buf()],
+        root_references,
+    )];
     let mut root_references = VecDeque::from(root_references);
     // search needs to be depth first to allow for overrides.
-    while let Some((mut reference_root, referenced_paths)) = root_references.pop_front() {
+    while let Some((mut reference_root, referencing_file, chain, referenced_paths)) =
+        root_references.pop_front()
+    {
         if reference_depth > SHADER_MAX_REFERENCE_DEPTH {
-            return Err(ParsePresetError::ExceededReferenceDepth);
+            return Err(ParsePresetError::ExceededReferenceDepth(referencing_file));
         }
         // enter the current root
         reference_depth += 1;
         // canonicalize current root
-        apply_context(&mut reference_root, context);
-        let reference_root = reference_root
-            .canonicalize()
+        state.apply_context(&mut reference_root, Some(&referencing_file));
+        let reference_root = state
+            .fs
+            .canonicalize(&reference_root)
             .map_err(|e| ParsePresetError::IOError(reference_root.to_path_buf(), e))?;
 
         // resolve all referenced paths against root
         // println!("Resolving {referenced_paths:?} against {reference_root:?}.");
 
-        for path in referenced_paths {
-            let mut path = reference_root.join(path.clone());
-            apply_context(&mut path, context);
+        for reference in referenced_paths {
+            let mut path = reference_root.join(&reference.path);
+            state.apply_context(&mut path, Some(&referencing_file));
+
+            let mut path = match state.fs.canonicalize(&path) {
+                Ok(path) => path,
+                Err(_) if state.diagnostics.is_some() => {
+                    state.diagnose(
+                        &referencing_file,
+                        reference.row,
+                        reference.col,
+                        PresetDiagnosticKind::MissingFile(path),
+                    );
+                    continue;
+                }
+                Err(e) => {
+                    return Err(ParsePresetError::MissingFile {
+                        file: referencing_file,
+                        path,
+                        source: e,
+                    })
+                }
+            };
+            // a preset that is already in the chain of references would be loaded forever.
+            if chain.contains(&path) {
+                let mut chain = chain.clone();
+                chain.push(path);
+                return Err(ParsePresetError::ReferenceCycle(chain));
+            }
 
-            let mut path = path
-                .canonicalize()
-                .map_err(|e| ParsePresetError::IOError(path.clone(), e))?;
             // println!("Opening {:?}", path);
-            let mut reference_contents = String::new();
-            File::open(&path)
-                .map_err(|e| ParsePresetError::IOError(path.clone(), e))?
-                .read_to_string(&mut reference_contents)
-                .map_err(|e| ParsePresetError::IOError(path.clone(), e))?;
-
-            let mut new_tokens = do_lex(&reference_contents)?;
-            let new_references: Vec<PathBuf> =
-                MakeExtractIf::extract_if(&mut new_tokens, |token| {
-                    *token.key.fragment() == "#reference"
-                })
-                .map(|value| PathBuf::from(*value.value.fragment()))
-                .collect();
+            let reference_contents = read_preset_string(&path, state.fs)?;
+            state.dependencies.push(Dependency {
+                kind: DependencyKind::Reference,
+                parent: referencing_file.clone(),
+                path: path.clone(),
+            });
+
+            let mut new_tokens = do_lex(&reference_contents, &path)?;
+            let new_references = PresetReference::extract(&mut new_tokens);
 
+            let file = path.clone();
             path.pop();
-            reference_strings.push_front((path.clone(), reference_contents));
             if !new_references.is_empty() {
-                root_references.push_front((path, new_references));
+                let mut chain = chain.clone();
+                chain.push(file.clone());
+                root_references.push_front((path.clone(), file.clone(), chain, new_references));
             }
+            reference_strings.push_front(ReferencedPreset {
+                dir: path,
+                file,
+                contents: reference_contents,
+            });
         }
     }
 
     Ok(reference_strings.into())
 }
 
+fn read_preset_string(path: &Path, fs: &dyn PresetFileSystem) -> Result<String, ParsePresetError> {
+    let bytes = fs
+        .read(path)
+        .map_err(|e| ParsePresetError::IOError(path.to_path_buf(), e))?;
+    String::from_utf8(bytes)
+        .map_err(|e| ParsePresetError::Utf8Error(path.to_path_buf(), e.into_bytes()))
+}
+
 pub(crate) fn parse_preset(
     path: impl AsRef<Path>,
     context: WildcardContext,
-) -> Result<Vec<Value>, ParsePresetError> {
+    fs: &dyn PresetFileSystem,
+    options: ParseOptions,
+) -> Result<ParsedPreset, ParsePresetError> {
     let path = path.as_ref();
     let mut path = path.to_path_buf();
-    let context = context.to_hashmap();
-
-    apply_context(&mut path, &context);
-
-    let path = path
-        .canonicalize()
+    let mut state = ParseState {
+        context: context.to_hashmap(),
+        fs,
+        dependencies: Vec::new(),
+        wildcards: Vec::new(),
+        search_roots: options.search_roots,
+        substitutions: Vec::new(),
+        diagnostics: options.lenient.then(Vec::new),
+    };
+
+    state.apply_context(&mut path, None);
+
+    let path = fs
+        .canonicalize(&path)
         .map_err(|e| ParsePresetError::IOError(path.to_path_buf(), e))?;
 
-    let mut contents = String::new();
-    File::open(&path)
-        .and_then(|mut f| f.read_to_string(&mut contents))
-        .map_err(|e| ParsePresetError::IOError(path.to_path_buf(), e))?;
+    // a bundle is parsed as the preset at its root.
+    #[cfg(feature = "archive")]
+    let path = crate::fs::archive::bundle_preset_path(path.clone(), fs)
+        .map_err(|e| ParsePresetError::IOError(path, e))?;
+
+    let contents = read_preset_string(&path, fs)?;
+
+    let tokens = super::token::do_lex(&contents, &path)?;
+    // paths in the preset are relative to the directory the preset is in.
+    let mut root_path = path.clone();
+    root_path.pop();
+
+    let values = parse_values(tokens, &root_path, &path, &mut state)?;
+    Ok(ParsedPreset {
+        values,
+        dependencies: DependencyGraph {
+            root: path,
+            dependencies: state.dependencies,
+        },
+        diagnostics: state.diagnostics.unwrap_or_default(),
+        wildcards: state.wildcards,
+        substitutions: state.substitutions,
+    })
+}
 
-    let tokens = super::token::do_lex(&contents)?;
-    parse_values(tokens, path, context)
+pub(crate) fn parse_preset_str(
+    contents: &str,
+    root_path: impl AsRef<Path>,
+    context: WildcardContext,
+    fs: &dyn PresetFileSystem,
+    options: ParseOptions,
+) -> Result<ParsedPreset, ParsePresetError> {
+    let root_path = root_path.as_ref();
+    let context = context.to_hashmap();
+
+    let root_path = fs
+        .canonicalize(root_path)
+        .map_err(|e| ParsePresetError::IOError(root_path.to_path_buf(), e))?;
+
+    let tokens = super::token::do_lex(contents, &root_path)?;
+    let mut state = ParseState {
+        context,
+        fs,
+        dependencies: Vec::new(),
+        wildcards: Vec::new(),
+        search_roots: options.search_roots,
+        substitutions: Vec::new(),
+        diagnostics: options.lenient.then(Vec::new),
+    };
+    let values = parse_values(tokens, &root_path, &root_path, &mut state)?;
+    Ok(ParsedPreset {
+        values,
+        dependencies: DependencyGraph {
+            root: root_path,
+            dependencies: state.dependencies,
+        },
+        diagnostics: state.diagnostics.unwrap_or_default(),
+        wildcards: state.wildcards,
+        substitutions: state.substitutions,
+    })
 }
 
-// prereq: root_path must be contextualized
-pub fn parse_values(
+// prereq: root_path must be contextualized, and the directory the preset is in.
+fn parse_values(
     mut tokens: Vec<Token>,
-    root_path: impl AsRef<Path>,
-    context: FastHashMap<String, String>,
+    root_path: &Path,
+    root_file: &Path,
+    state: &mut ParseState,
 ) -> Result<Vec<Value>, ParsePresetError> {
-    let mut root_path = root_path.as_ref().to_path_buf();
-    if root_path.is_relative() {
-        return Err(ParsePresetError::RootPathWasNotAbsolute);
-    }
-    if !root_path.is_dir() {
-        // we don't really care if this doesn't do anything because a non-canonical root path will
-        // fail at a later stage during resolution.
-        root_path.pop();
+    if !root_path.has_root() {
+        return Err(ParsePresetError::RootPathWasNotAbsolute(
+            root_path.to_path_buf(),
+        ));
     }
 
-    let references: Vec<PathBuf> =
-        MakeExtractIf::extract_if(&mut tokens, |token| *token.key.fragment() == "#reference")
-            .map(|value| PathBuf::from(*value.value.fragment()))
-            .collect();
+    let references = PresetReference::extract(&mut tokens);
 
     // unfortunately we need to lex twice because there's no way to know the references ahead of time.
     // the returned references should have context applied
 
-    let child_strings = load_child_reference_strings(references, &root_path, &context)?;
-    let mut all_tokens: Vec<(&Path, Vec<Token>)> = Vec::new();
+    let child_strings = load_child_reference_strings(references, root_path, root_file, state)?;
+    let mut all_tokens: Vec<(TokenSource, Vec<Token>)> = Vec::new();
 
-    for (path, string) in child_strings.iter() {
+    for child in child_strings.iter() {
         // lex the child tokens
-        let mut tokens = do_lex(string.as_ref())?;
+        let mut tokens = do_lex(child.contents.as_ref(), &child.file)?;
         tokens.retain(|token| *token.key.fragment() != "#reference");
-        all_tokens.push((path.as_path(), tokens))
+        let source = TokenSource {
+            dir: &child.dir,
+            file: &child.file,
+        };
+        state.check_duplicate_keys(source, &tokens);
+        all_tokens.push((source, tokens))
     }
 
     // load depth first, so all child tokens are first.
     // Later tokens take precedence.
-    all_tokens.push((root_path.as_path(), tokens));
+    let source = TokenSource {
+        dir: root_path,
+        file: root_file,
+    };
+    state.check_duplicate_keys(source, &tokens);
+    all_tokens.push((source, tokens));
 
     // collect all possible parameter names.
     let mut parameter_names: Vec<&str> = Vec::new();
@@ -301,7 +685,7 @@ pub fn parse_values(
 
     let mut values = Vec::new();
     // resolve shader paths.
-    for (path, tokens) in all_tokens.iter_mut() {
+    for (source, tokens) in all_tokens.iter_mut() {
         for token in MakeExtractIf::extract_if(tokens, |token| {
             parse_indexed_key("shader", token.key).is_ok()
         }) {
@@ -309,6 +693,7 @@ pub fn parse_values(
                 nom::Err::Error(e) | nom::Err::Failure(e) => {
                     let input: Span = e.input;
                     ParsePresetError::ParserError {
+                        file: source.file.to_path_buf(),
                         offset: input.location_offset(),
                         row: input.location_line(),
                         col: input.get_column(),
@@ -316,6 +701,7 @@ pub fn parse_values(
                     }
                 }
                 _ => ParsePresetError::ParserError {
+                    file: source.file.to_path_buf(),
                     offset: 0,
                     row: 0,
                     col: 0,
@@ -323,32 +709,56 @@ pub fn parse_values(
                 },
             })?;
 
-            let mut relative_path = path.to_path_buf();
+            // built-in shaders are never read from the filesystem.
+            if is_builtin_path(token.value.fragment()) {
+                let path = PathBuf::from(*token.value.fragment());
+                if BuiltinShader::from_path(&path).is_none() {
+                    state.check_exists(&path, *source, token.value)?;
+                }
+                values.push(Value::Shader(index, path));
+                continue;
+            }
+
+            let mut relative_path = source.dir.to_path_buf();
             relative_path.push(*token.value.fragment());
-            relative_path
-                .canonicalize()
-                .map_err(|e| ParsePresetError::IOError(relative_path.clone(), e))?;
+            state.apply_context(&mut relative_path, Some(source.file));
+            state.search_roots(&mut relative_path, token.value.fragment(), source.file);
+            if let Some(builtin) = state.builtin_fallback(&relative_path, *source, token.value) {
+                values.push(Value::Shader(index, builtin.path()));
+                continue;
+            }
+            state.check_exists(&relative_path, *source, token.value)?;
+            state.dependencies.push(Dependency {
+                kind: DependencyKind::Shader(index),
+                parent: source.file.to_path_buf(),
+                path: normalize_path(&relative_path),
+            });
             values.push(Value::Shader(index, relative_path))
         }
     }
 
     // resolve texture paths
     let mut textures = Vec::new();
-    for (path, tokens) in all_tokens.iter_mut() {
+    for (source, tokens) in all_tokens.iter_mut() {
         for token in
             MakeExtractIf::extract_if(tokens, |token| texture_names.contains(token.key.fragment()))
         {
-            let mut relative_path = path.to_path_buf();
+            let mut relative_path = source.dir.to_path_buf();
             // Don't trim paths
             relative_path.push(*token.value.fragment());
-            relative_path
-                .canonicalize()
-                .map_err(|e| ParsePresetError::IOError(relative_path.clone(), e))?;
+            state.apply_context(&mut relative_path, Some(source.file));
+            state.search_roots(&mut relative_path, token.value.fragment(), source.file);
+            state.check_exists(&relative_path, *source, token.value)?;
+            state.dependencies.push(Dependency {
+                kind: DependencyKind::Texture(ShortString::from(*token.key.fragment())),
+                parent: source.file.to_path_buf(),
+                path: normalize_path(&relative_path),
+            });
             textures.push((token.key, relative_path))
         }
     }
 
-    let mut tokens: Vec<(&Path, Token)> = all_tokens
+    let mut tokens: Vec<(TokenSource, Token)> = all_tokens
         .into_iter()
         .flat_map(|(p, token)| token.into_iter().map(move |t| (p, t)))
         .collect();
@@ -359,14 +769,22 @@ pub fn parse_values(
                 && t.key.ends_with("_mipmap")
                 && t.key.len() == texture.len() + "_mipmap".len()
         })
-        .map_or_else(|| Ok(false), |(_, v)| from_bool(v.value))?;
+        .map_or_else(
+            || Ok(Some(false)),
+            |(source, v)| state.recover(from_bool(v.value), source, &v),
+        )?
+        .unwrap_or(false);
 
         let linear = remove_if(&mut tokens, |(_, t)| {
             t.key.starts_with(*texture)
                 && t.key.ends_with("_linear")
                 && t.key.len() == texture.len() + "_linear".len()
         })
-        .map_or_else(|| Ok(false), |(_, v)| from_bool(v.value))?;
+        .map_or_else(
+            || Ok(Some(false)),
+            |(source, v)| state.recover(from_bool(v.value), source, &v),
+        )?
+        .unwrap_or(false);
 
         let wrap_mode = remove_if(&mut tokens, |(_, t)| {
             t.key.starts_with(*texture)
@@ -374,9 +792,8 @@ pub fn parse_values(
                 && (t.key.len() == texture.len() + "_wrap_mode".len()
                     || t.key.len() == texture.len() + "_repeat_mode".len())
         })
-        // NOPANIC: infallible
-        .map_or_else(WrapMode::default, |(_, v)| {
-            WrapMode::from_str(&v.value).unwrap()
+        .map_or_else(WrapMode::default, |(source, v)| {
+            state.parse_wrap_mode(source, &v)
         });
 
         // This really isn't supported but crt-torridgristle uses this syntax.
@@ -386,8 +803,7 @@ pub fn parse_values(
                 && t.key.ends_with(*texture)
                 && t.key.len() == "filter_".len() + texture.len()
         })
-        // NOPANIC: infallible
-        .map_or(None, |(_, v)| Some(FilterMode::from_str(&v.value).unwrap()));
+        .map(|(source, v)| state.parse_filter_mode(source, &v));
 
         values.push(Value::Texture {
             name: ShortString::from(*texture.fragment()),
@@ -404,14 +820,25 @@ pub fn parse_values(
 
     let mut rest_tokens = Vec::new();
     // hopefully no more textures left in the token tree
-    for (p, token) in tokens {
+    for (source, token) in tokens {
         if parameter_names.contains(&token.key.fragment().trim()) {
             let param_val = from_float(token.value)
                 // This is literally just to work around BEAM_PROFILE in crt-hyllian-sinc-glow.slangp
                 // which has ""0'.000000". This somehow works in RA because it defaults to 0, probably.
                 // This hack is only used for **known** parameter names. If we tried this for undeclared
                 // params (god help me), it would be pretty bad because we lose texture path fallback.
-                .unwrap_or(0.0);
+                .unwrap_or_else(|_| {
+                    state.diagnose_at(
+                        source.file,
+                        token.value,
+                        PresetDiagnosticKind::CoercedValue {
+                            key: token.key.fragment().trim().to_string(),
+                            value: token.value.fragment().to_string(),
+                            coerced: String::from("0"),
+                        },
+                    );
+                    0.0
+                });
             values.push(Value::Parameter(
                 ShortString::from(token.key.fragment().trim()),
                 param_val,
@@ -419,37 +846,40 @@ pub fn parse_values(
             continue;
         }
         if token.key.fragment() == &"shaders" {
-            let shader_count = from_int(token.value)?;
-            values.push(Value::ShaderCount(shader_count));
+            if let Some(shader_count) = state.recover(from_int(token.value), source, &token)? {
+                values.push(Value::ShaderCount(shader_count));
+            }
             continue;
         }
         if token.key.fragment() == &"feedback_pass" {
-            let feedback_pass = from_int(token.value)?;
-            values.push(Value::FeedbackPass(feedback_pass));
+            if let Some(feedback_pass) = state.recover(from_int(token.value), source, &token)? {
+                values.push(Value::FeedbackPass(feedback_pass));
+            }
             continue;
         }
         if let Ok((_, idx)) = parse_indexed_key("filter_linear", token.key) {
-            let linear = from_bool(token.value)?;
-            values.push(Value::FilterMode(
-                idx,
-                if linear {
-                    FilterMode::Linear
-                } else {
-                    FilterMode::Nearest
-                },
-            ));
+            if let Some(linear) = state.recover(from_bool(token.value), source, &token)? {
+                values.push(Value::FilterMode(
+                    idx,
+                    if linear {
+                        FilterMode::Linear
+                    } else {
+                        FilterMode::Nearest
+                    },
+                ));
+            }
             continue;
         }
 
         if let Ok((_, idx)) = parse_indexed_key("wrap_mode", token.key) {
-            let wrap_mode = WrapMode::from_str(&token.value).unwrap();
+            let wrap_mode = state.parse_wrap_mode(source, &token);
             values.push(Value::WrapMode(idx, wrap_mode));
             continue;
         }
 
         // crt-geom uses repeat_mode...
         if let Ok((_, idx)) = parse_indexed_key("repeat_mode", token.key) {
-            let wrap_mode = WrapMode::from_str(&token.value).unwrap();
+            let wrap_mode = state.parse_wrap_mode(source, &token);
             values.push(Value::WrapMode(idx, wrap_mode));
             continue;
         }
@@ -457,39 +887,44 @@ pub fn parse_values(
         // crt-royale uses 'texture_wrap_mode' instead of 'wrap_mode', I have no idea
         // how this possibly could work in RA, but here it is..
         if let Ok((_, idx)) = parse_indexed_key("texture_wrap_mode", token.key) {
-            let wrap_mode = WrapMode::from_str(&token.value).unwrap();
+            let wrap_mode = state.parse_wrap_mode(source, &token);
             values.push(Value::WrapMode(idx, wrap_mode));
             continue;
         }
 
         if let Ok((_, idx)) = parse_indexed_key("frame_count_mod", token.key) {
-            let frame_count_mod = from_ul(token.value)?;
-            values.push(Value::FrameCountMod(idx, frame_count_mod));
+            if let Some(frame_count_mod) = state.recover(from_ul(token.value), source, &token)? {
+                values.push(Value::FrameCountMod(idx, frame_count_mod));
+            }
             continue;
         }
 
         if let Ok((_, idx)) = parse_indexed_key("srgb_framebuffer", token.key) {
-            let enabled = from_bool(token.value)?;
-            values.push(Value::SrgbFramebuffer(idx, enabled));
+            if let Some(enabled) = state.recover(from_bool(token.value), source, &token)? {
+                values.push(Value::SrgbFramebuffer(idx, enabled));
+            }
             continue;
         }
 
         if let Ok((_, idx)) = parse_indexed_key("float_framebuffer", token.key) {
-            let enabled = from_bool(token.value)?;
-            values.push(Value::FloatFramebuffer(idx, enabled));
+            if let Some(enabled) = state.recover(from_bool(token.value), source, &token)? {
+                values.push(Value::FloatFramebuffer(idx, enabled));
+            }
             continue;
         }
 
         if let Ok((_, idx)) = parse_indexed_key("mipmap_input", token.key) {
-            let enabled = from_bool(token.value)?;
-            values.push(Value::MipmapInput(idx, enabled));
+            if let Some(enabled) = state.recover(from_bool(token.value), source, &token)? {
+                values.push(Value::MipmapInput(idx, enabled));
+            }
             continue;
         }
 
         // vector-glow-alt-render.slangp uses "mipmap" for pass 1, but "mipmap_input" for everything else.
         if let Ok((_, idx)) = parse_indexed_key("mipmap", token.key) {
-            let enabled = from_bool(token.value)?;
-            values.push(Value::MipmapInput(idx, enabled));
+            if let Some(enabled) = state.recover(from_bool(token.value), source, &token)? {
+                values.push(Value::MipmapInput(idx, enabled));
+            }
             continue;
         }
 
@@ -500,60 +935,73 @@ pub fn parse_values(
             ));
             continue;
         }
+        if let Ok((_, idx)) = parse_indexed_key("defines", token.key) {
+            if let Some(defines) = state.recover(from_defines(token.value), source, &token)? {
+                values.push(Value::Defines(idx, defines));
+            }
+            continue;
+        }
         if let Ok((_, idx)) = parse_indexed_key("scale_type", token.key) {
-            let scale_type = ScaleType::from_str(token.value.trim())?;
-            values.push(Value::ScaleType(idx, scale_type));
+            let scale_type = from_scale_type(token.value);
+            if let Some(scale_type) = state.recover(scale_type, source, &token)? {
+                values.push(Value::ScaleType(idx, scale_type));
+            }
             continue;
         }
         if let Ok((_, idx)) = parse_indexed_key("scale_type_x", token.key) {
-            let scale_type = ScaleType::from_str(token.value.trim())?;
-            values.push(Value::ScaleTypeX(idx, scale_type));
+            let scale_type = from_scale_type(token.value);
+            if let Some(scale_type) = state.recover(scale_type, source, &token)? {
+                values.push(Value::ScaleTypeX(idx, scale_type));
+            }
             continue;
         }
         if let Ok((_, idx)) = parse_indexed_key("scale_type_y", token.key) {
-            let scale_type = ScaleType::from_str(token.value.trim())?;
-            values.push(Value::ScaleTypeY(idx, scale_type));
+            let scale_type = from_scale_type(token.value);
+            if let Some(scale_type) = state.recover(scale_type, source, &token)? {
+                values.push(Value::ScaleTypeY(idx, scale_type));
+            }
             continue;
         }
-        rest_tokens.push((p, token))
+        rest_tokens.push((source, token))
     }
 
     let mut undeclared_textures = Vec::new();
-    for (path, token) in &rest_tokens {
+    let mut unrecognized_keys = Vec::new();
+    for (source, token) in &rest_tokens {
         if let Ok((_, idx)) = parse_indexed_key("scale", token.key) {
             let scale = if values.iter().any(|t| matches!(*t, Value::ScaleType(match_idx, ScaleType::Absolute) if match_idx == idx)) {
-                let scale = from_int(token.value)?;
-                ScaleFactor::Absolute(scale)
+                from_int(token.value).map(ScaleFactor::Absolute)
             } else {
-                let scale = from_float(token.value)?;
-                ScaleFactor::Float(scale)
+                from_float(token.value).map(ScaleFactor::Float)
             };
 
-            values.push(Value::Scale(idx, scale));
+            if let Some(scale) = state.recover(scale, *source, token)? {
+                values.push(Value::Scale(idx, scale));
+            }
             continue;
         }
         if let Ok((_, idx)) = parse_indexed_key("scale_x", token.key) {
             let scale = if values.iter().any(|t| matches!(*t, Value::ScaleType(match_idx, ScaleType::Absolute) | Value::ScaleTypeX(match_idx, ScaleType::Absolute) if match_idx == idx)) {
-                let scale = from_int(token.value)?;
-                ScaleFactor::Absolute(scale)
+                from_int(token.value).map(ScaleFactor::Absolute)
             } else {
-                let scale = from_float(token.value)?;
-                ScaleFactor::Float(scale)
+                from_float(token.value).map(ScaleFactor::Float)
             };
 
-            values.push(Value::ScaleX(idx, scale));
+            if let Some(scale) = state.recover(scale, *source, token)? {
+                values.push(Value::ScaleX(idx, scale));
+            }
             continue;
         }
         if let Ok((_, idx)) = parse_indexed_key("scale_y", token.key) {
             let scale = if values.iter().any(|t| matches!(*t, Value::ScaleType(match_idx, ScaleType::Absolute) | Value::ScaleTypeY(match_idx, ScaleType::Absolute) if match_idx == idx)) {
-                let scale = from_int(token.value)?;
-                ScaleFactor::Absolute(scale)
+                from_int(token.value).map(ScaleFactor::Absolute)
             } else {
-                let scale = from_float(token.value)?;
-                ScaleFactor::Float(scale)
+                from_float(token.value).map(ScaleFactor::Float)
             };
 
-            values.push(Value::ScaleY(idx, scale));
+            if let Some(scale) = state.recover(scale, *source, token)? {
+                values.push(Value::ScaleY(idx, scale));
+            }
             continue;
         }
 
@@ -570,33 +1018,49 @@ pub fn parse_values(
                 .iter()
                 .all(|k| !token.key.ends_with(k))
         {
-            let mut relative_path = path.to_path_buf();
+            let mut relative_path = source.dir.to_path_buf();
             // Don't trim paths.
             relative_path.push(*token.value.fragment());
-            relative_path
-                .canonicalize()
-                .map_err(|e| ParsePresetError::IOError(relative_path.clone(), e))?;
+            state.apply_context(&mut relative_path, Some(source.file));
+            state.search_roots(&mut relative_path, token.value.fragment(), source.file);
+            state.check_exists(&relative_path, *source, token.value)?;
+            state.dependencies.push(Dependency {
+                kind: DependencyKind::Texture(ShortString::from(*token.key.fragment())),
+                parent: source.file.to_path_buf(),
+                path: normalize_path(&relative_path),
+            });
             undeclared_textures.push((token.key, relative_path));
+        } else {
+            // we tried our best
+            unrecognized_keys.push((*source, token.key));
         }
-
-        // we tried our best
     }
 
     // Since there are undeclared textures we need to deal with potential mipmap information.
+    let undeclared_texture_names: Vec<Span> =
+        undeclared_textures.iter().map(|(name, _)| *name).collect();
     for (texture, path) in undeclared_textures {
         let mipmap = remove_if(&mut rest_tokens, |(_, t)| {
             t.key.starts_with(*texture)
                 && t.key.ends_with("_mipmap")
                 && t.key.len() == texture.len() + "_mipmap".len()
         })
-        .map_or_else(|| Ok(false), |(_, v)| from_bool(v.value))?;
+        .map_or_else(
+            || Ok(Some(false)),
+            |(source, v)| state.recover(from_bool(v.value), source, &v),
+        )?
+        .unwrap_or(false);
 
         let linear = remove_if(&mut rest_tokens, |(_, t)| {
             t.key.starts_with(*texture)
                 && t.key.ends_with("_linear")
                 && t.key.len() == texture.len() + "_linear".len()
         })
-        .map_or_else(|| Ok(false), |(_, v)| from_bool(v.value))?;
+        .map_or_else(
+            || Ok(Some(false)),
+            |(source, v)| state.recover(from_bool(v.value), source, &v),
+        )?
+        .unwrap_or(false);
 
         let wrap_mode = remove_if(&mut rest_tokens, |(_, t)| {
             t.key.starts_with(*texture)
@@ -604,9 +1068,8 @@ pub fn parse_values(
                 && (t.key.len() == texture.len() + "_wrap_mode".len()
                     || t.key.len() == texture.len() + "_repeat_mode".len())
         })
-        // NOPANIC: infallible
-        .map_or_else(WrapMode::default, |(_, v)| {
-            WrapMode::from_str(&v.value).unwrap()
+        .map_or_else(WrapMode::default, |(source, v)| {
+            state.parse_wrap_mode(source, &v)
         });
 
         values.push(Value::Texture {
@@ -622,13 +1085,30 @@ pub fn parse_values(
         })
     }
 
+    // keys that were not used by an undeclared texture are unknown.
+    for (source, key) in unrecognized_keys {
+        let is_texture_key = undeclared_texture_names.iter().any(|texture| {
+            ["_mipmap", "_linear", "_wrap_mode", "_repeat_mode"]
+                .iter()
+                .any(|suffix| key.strip_prefix(**texture) == Some(*suffix))
+        });
+        if !is_texture_key {
+            state.diagnose_at(
+                source.file,
+                key,
+                PresetDiagnosticKind::UnknownKey(key.fragment().to_string()),
+            );
+        }
+    }
+
     // all tokens should be ok to process now.
     Ok(values)
 }
 
 #[cfg(test)]
 mod test {
-    use crate::parse::value::parse_preset;
+    use crate::fs::DiskFileSystem;
+    use crate::parse::value::{parse_preset, ParseOptions};
     use crate::WildcardContext;
     use std::path::PathBuf;
 
@@ -636,7 +1116,13 @@ mod test {
     pub fn parse_basic() {
         let root =
             PathBuf::from("../test/shaders_slang/bezel/Mega_Bezel/Presets/Base_CRT_Presets/MBZ__3__STD__MEGATRON-NTSC.slangp");
-        let basic = parse_preset(root, WildcardContext::new());
+        let basic = parse_preset(
+            root,
+            WildcardContext::new(),
+            &DiskFileSystem,
+            ParseOptions::default(),
+        )
+        .map(|parsed| parsed.values);
         eprintln!("{basic:?}");
         assert!(basic.is_ok());
     }
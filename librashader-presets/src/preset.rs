@@ -1,12 +1,14 @@
 use crate::error::ParsePresetError;
 use librashader_common::map::ShortString;
 use librashader_common::{FilterMode, ImageFormat, WrapMode};
+use std::fmt::{Display, Formatter};
 use std::ops::Mul;
 use std::path::PathBuf;
 use std::str::FromStr;
 
 /// The configuration for a single shader pass.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ShaderPassConfig {
     /// The index of the shader pass relative to its parent preset.
     pub id: i32,
@@ -28,6 +30,11 @@ pub struct ShaderPassConfig {
     pub mipmap_input: bool,
     /// Specifies the scaling of the output framebuffer for this shader pass.
     pub scaling: Scale2D,
+    /// Preprocessor definitions to inject into the source of this shader pass.
+    ///
+    /// Definitions are part of the shader source once loaded, so the same shader compiled with
+    /// different definitions is cached separately.
+    pub defines: Vec<ShaderDefine>,
 }
 
 impl ShaderPassConfig {
@@ -43,6 +50,27 @@ impl ShaderPassConfig {
         None
     }
 
+    /// Create the configuration for a shader pass with the source at the given path.
+    ///
+    /// The remaining options are set to the same defaults used when they are not specified in
+    /// a preset. The index of the pass is set when the pass is added to a preset with
+    /// [`ShaderPresetBuilder`](crate::ShaderPresetBuilder).
+    pub fn new(name: impl Into<PathBuf>) -> Self {
+        ShaderPassConfig {
+            id: 0,
+            name: name.into(),
+            alias: None,
+            filter: FilterMode::default(),
+            wrap_mode: WrapMode::default(),
+            frame_count_mod: 0,
+            srgb_framebuffer: false,
+            float_framebuffer: false,
+            mipmap_input: false,
+            scaling: Scale2D::default(),
+            defines: Vec::new(),
+        }
+    }
+
     #[inline(always)]
     pub fn get_frame_count(&self, count: usize) -> u32 {
         (if self.frame_count_mod > 0 {
@@ -53,8 +81,55 @@ impl ShaderPassConfig {
     }
 }
 
+/// A preprocessor definition to inject into a shader source, as `#define name value`.
+///
+/// In a preset, definitions for a pass are written as a `;` separated list of `NAME=VALUE`,
+/// such as `defines0 = "USE_FAST_PATH=1;LIBRASHADER_TARGET_WGSL"`. Like the `-D` option of a
+/// C compiler, a definition without a value is defined as `1`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ShaderDefine {
+    /// The name of the macro.
+    pub name: ShortString,
+    /// The value the macro expands to.
+    pub value: String,
+}
+
+impl ShaderDefine {
+    /// Create a definition of the macro `name` that expands to `value`.
+    pub fn new(name: impl Into<ShortString>, value: impl Into<String>) -> Self {
+        ShaderDefine {
+            name: name.into(),
+            value: value.into(),
+        }
+    }
+}
+
+impl FromStr for ShaderDefine {
+    type Err = ParsePresetError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, value) = s.split_once('=').unwrap_or((s, "1"));
+        let name = name.trim();
+        if name.is_empty()
+            || name.starts_with(|c: char| c.is_ascii_digit())
+            || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        {
+            return Err(ParsePresetError::InvalidDefine(s.to_string()));
+        }
+        Ok(ShaderDefine::new(name, value.trim()))
+    }
+}
+
+impl Display for ShaderDefine {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}={}", self.name, self.value)
+    }
+}
+
 #[repr(i32)]
-#[derive(Default, Copy, Clone, Debug)]
+#[derive(Default, Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// The scaling type for the shader pass.
 pub enum ScaleType {
     #[default]
@@ -69,7 +144,8 @@ pub enum ScaleType {
 }
 
 /// The scaling factor for framebuffer scaling.
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ScaleFactor {
     /// Scale by a fractional float factor.
     Float(f32),
@@ -128,8 +204,29 @@ impl FromStr for ScaleType {
     }
 }
 
+impl Display for ScaleType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ScaleType::Input => f.write_str("source"),
+            ScaleType::Absolute => f.write_str("absolute"),
+            ScaleType::Viewport => f.write_str("viewport"),
+            ScaleType::Original => f.write_str("original"),
+        }
+    }
+}
+
+impl Display for ScaleFactor {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ScaleFactor::Float(factor) => write!(f, "{factor}"),
+            ScaleFactor::Absolute(factor) => write!(f, "{factor}"),
+        }
+    }
+}
+
 /// Framebuffer scaling parameters.
-#[derive(Debug, Clone)]
+#[derive(Debug, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Scaling {
     /// The method to scale the framebuffer with.
     pub scale_type: ScaleType,
@@ -138,7 +235,8 @@ pub struct Scaling {
 }
 
 /// 2D quad scaling parameters.
-#[derive(Debug, Clone)]
+#[derive(Debug, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Scale2D {
     /// Whether or not this combination of scaling factors is valid.
     pub valid: bool,
@@ -149,7 +247,8 @@ pub struct Scale2D {
 }
 
 /// Configuration options for a lookup texture used in the shader.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TextureConfig {
     /// The name of the texture.
     pub name: ShortString,
@@ -164,7 +263,8 @@ pub struct TextureConfig {
 }
 
 /// Configuration options for a shader parameter.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ParameterConfig {
     /// The name of the parameter.
     pub name: ShortString,
@@ -176,7 +276,8 @@ pub struct ParameterConfig {
 ///
 /// A shader preset can be used to create a filter chain runtime instance, or reflected to get
 /// parameter metadata.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ShaderPreset {
     /// Used in legacy GLSL shader semantics. If < 0, no feedback pass is used.
     /// Otherwise, the FBO after pass #N is passed a texture to next frame
@@ -195,3 +296,16 @@ pub struct ShaderPreset {
     /// Preset information for each user parameter.
     pub parameters: Vec<ParameterConfig>,
 }
+
+#[cfg(all(test, feature = "serde"))]
+mod test {
+    use crate::ShaderPreset;
+
+    #[test]
+    fn round_trips_json() {
+        let preset = ShaderPreset::try_parse("../test/basic.slangp").unwrap();
+        let json = serde_json::to_string(&preset).unwrap();
+        let deserialized: ShaderPreset = serde_json::from_str(&json).unwrap();
+        assert_eq!(preset, deserialized);
+    }
+}
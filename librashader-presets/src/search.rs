@@ -0,0 +1,35 @@
+use std::path::{Component, Path, PathBuf};
+
+/// A shader or texture path in a shader preset that could not be found relative to the preset,
+/// and was found under a fallback search root instead.
+///
+/// A missing `stock.slang` shader is replaced with the built-in shader, which is reported
+/// with `builtin:` as the search root.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchRootSubstitution {
+    /// The preset file the path was written in.
+    pub file: PathBuf,
+    /// The path relative to the preset, which could not be found.
+    pub original: PathBuf,
+    /// The search root the path was found under, or `builtin:` for a built-in shader.
+    pub root: PathBuf,
+    /// The path that was used instead.
+    pub resolved: PathBuf,
+}
+
+/// Get the paths to try under a search root for a path written in a preset.
+///
+/// The path is first tried as written. Since presets usually refer to files that moved
+/// with paths like `../stock.slang`, it is then tried without its leading `.` and `..`
+/// components.
+pub(crate) fn search_root_candidates(root: &Path, written: &Path) -> Vec<PathBuf> {
+    let mut candidates = vec![root.join(written)];
+    let stripped: PathBuf = written
+        .components()
+        .skip_while(|component| matches!(component, Component::CurDir | Component::ParentDir))
+        .collect();
+    if !stripped.as_os_str().is_empty() && stripped != written {
+        candidates.push(root.join(stripped));
+    }
+    candidates
+}
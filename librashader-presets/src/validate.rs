@@ -0,0 +1,279 @@
+use crate::error::{PresetValidationError, ScaleAxis};
+use crate::{ScaleFactor, ScaleType, Scaling, ShaderPreset};
+
+/// Texture semantics that are bound by name.
+const RESERVED_NAMES: [&str; 2] = ["Original", "Source"];
+
+/// Texture semantics that are bound by name, followed by an index.
+const RESERVED_INDEXED_NAMES: [&str; 4] = ["OriginalHistory", "PassOutput", "PassFeedback", "User"];
+
+fn is_reserved_name(name: &str) -> bool {
+    RESERVED_NAMES.contains(&name)
+        || RESERVED_INDEXED_NAMES.iter().any(|prefix| {
+            name.strip_prefix(prefix)
+                .is_some_and(|index| index.bytes().all(|b| b.is_ascii_digit()))
+        })
+}
+
+fn validate_scaling(
+    pass: i32,
+    axis: ScaleAxis,
+    scaling: &Scaling,
+    errors: &mut Vec<PresetValidationError>,
+) {
+    let absolute_type = scaling.scale_type == ScaleType::Absolute;
+    let valid = match scaling.factor {
+        ScaleFactor::Absolute(factor) => {
+            if !absolute_type {
+                errors.push(PresetValidationError::ScaleFactorMismatch { pass, axis });
+            }
+            factor > 0
+        }
+        ScaleFactor::Float(factor) => {
+            if absolute_type {
+                errors.push(PresetValidationError::ScaleFactorMismatch { pass, axis });
+            }
+            factor.is_finite() && factor > 0.0
+        }
+    };
+
+    if !valid {
+        errors.push(PresetValidationError::InvalidScaleFactor { pass, axis });
+    }
+}
+
+impl ShaderPreset {
+    /// Check the shader preset for consistency, without loading or compiling any shaders.
+    ///
+    /// Returns every inconsistency found. An empty list means the preset is consistent.
+    /// Parameters are not checked, since that requires the parameters declared by the shaders;
+    /// see [`ShaderPreset::validate_parameters`].
+    pub fn validate(&self) -> Vec<PresetValidationError> {
+        let mut errors = Vec::new();
+
+        for index in 0..self.shader_count {
+            if !self.shaders.iter().any(|shader| shader.id == index) {
+                errors.push(PresetValidationError::MissingPass(index));
+            }
+        }
+
+        for (position, shader) in self.shaders.iter().enumerate() {
+            let id = shader.id;
+            if id < 0 || id >= self.shader_count {
+                errors.push(PresetValidationError::PassOutOfRange(id));
+            }
+            if self.shaders[..position].iter().any(|other| other.id == id) {
+                errors.push(PresetValidationError::DuplicatePass(id));
+            }
+
+            if let Some(alias) = &shader.alias {
+                if alias.trim().is_empty() {
+                    errors.push(PresetValidationError::EmptyAlias(id));
+                } else {
+                    if is_reserved_name(alias) {
+                        errors.push(PresetValidationError::ReservedAlias {
+                            pass: id,
+                            alias: alias.clone(),
+                        });
+                    }
+                    if let Some(first) = self.shaders[..position]
+                        .iter()
+                        .find(|other| other.alias.as_ref() == Some(alias))
+                    {
+                        errors.push(PresetValidationError::DuplicateAlias {
+                            alias: alias.clone(),
+                            first: first.id,
+                            second: id,
+                        });
+                    }
+                }
+            }
+
+            let scaling = &shader.scaling;
+            if scaling.valid {
+                validate_scaling(id, ScaleAxis::X, &scaling.x, &mut errors);
+                validate_scaling(id, ScaleAxis::Y, &scaling.y, &mut errors);
+            } else if scaling.x.factor != ScaleFactor::default()
+                || scaling.y.factor != ScaleFactor::default()
+            {
+                errors.push(PresetValidationError::ScaleWithoutType(id));
+            }
+        }
+
+        for texture in &self.textures {
+            if let Some(shader) = self
+                .shaders
+                .iter()
+                .find(|shader| shader.alias.as_ref() == Some(&texture.name))
+            {
+                errors.push(PresetValidationError::TextureShadowsAlias {
+                    texture: texture.name.clone(),
+                    pass: shader.id,
+                });
+            }
+        }
+
+        errors
+    }
+
+    /// Check that every parameter set in the preset is declared by a shader.
+    ///
+    /// `declared` are the names of the parameters declared by the shaders in the preset.
+    pub fn validate_parameters<'a>(
+        &self,
+        declared: impl IntoIterator<Item = &'a str>,
+    ) -> Vec<PresetValidationError> {
+        let declared: Vec<&str> = declared.into_iter().collect();
+        self.parameters
+            .iter()
+            .filter(|parameter| !declared.contains(&parameter.name.as_str()))
+            .map(|parameter| PresetValidationError::UndeclaredParameter(parameter.name.clone()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        ParameterConfig, PresetValidationError, Scale2D, ScaleAxis, ScaleFactor, ScaleType,
+        Scaling, ShaderPassConfig, ShaderPreset, TextureConfig,
+    };
+    use librashader_common::{FilterMode, WrapMode};
+    use std::path::PathBuf;
+
+    fn pass(id: i32, alias: Option<&str>) -> ShaderPassConfig {
+        ShaderPassConfig {
+            id,
+            name: PathBuf::from(format!("pass{id}.slang")),
+            alias: alias.map(Into::into),
+            filter: FilterMode::Nearest,
+            wrap_mode: WrapMode::ClampToBorder,
+            frame_count_mod: 0,
+            srgb_framebuffer: false,
+            float_framebuffer: false,
+            mipmap_input: false,
+            defines: Vec::new(),
+            scaling: Scale2D {
+                valid: false,
+                x: Scaling {
+                    scale_type: ScaleType::Input,
+                    factor: ScaleFactor::default(),
+                },
+                y: Scaling {
+                    scale_type: ScaleType::Input,
+                    factor: ScaleFactor::default(),
+                },
+            },
+        }
+    }
+
+    fn preset(shader_count: i32, shaders: Vec<ShaderPassConfig>) -> ShaderPreset {
+        ShaderPreset {
+            #[cfg(feature = "parse_legacy_glsl")]
+            feedback_pass: -1,
+            shader_count,
+            shaders,
+            textures: vec![],
+            parameters: vec![],
+        }
+    }
+
+    #[test]
+    fn valid_preset() {
+        let preset = preset(2, vec![pass(0, Some("First")), pass(1, None)]);
+        assert!(preset.validate().is_empty());
+    }
+
+    #[test]
+    fn finds_pass_and_alias_errors() {
+        let mut preset = preset(
+            3,
+            vec![
+                pass(0, Some("Blur")),
+                pass(1, Some("Blur")),
+                pass(1, Some("PassOutput1")),
+                pass(3, Some(" ")),
+            ],
+        );
+        preset.textures.push(TextureConfig {
+            name: "Blur".into(),
+            path: PathBuf::from("blur.png"),
+            wrap_mode: WrapMode::ClampToBorder,
+            filter_mode: FilterMode::Nearest,
+            mipmap: false,
+        });
+
+        assert_eq!(
+            vec![
+                PresetValidationError::MissingPass(2),
+                PresetValidationError::DuplicateAlias {
+                    alias: "Blur".into(),
+                    first: 0,
+                    second: 1,
+                },
+                PresetValidationError::DuplicatePass(1),
+                PresetValidationError::ReservedAlias {
+                    pass: 1,
+                    alias: "PassOutput1".into(),
+                },
+                PresetValidationError::PassOutOfRange(3),
+                PresetValidationError::EmptyAlias(3),
+                PresetValidationError::TextureShadowsAlias {
+                    texture: "Blur".into(),
+                    pass: 0,
+                },
+            ],
+            preset.validate()
+        );
+    }
+
+    #[test]
+    fn finds_scaling_errors() {
+        let mut without_type = pass(0, None);
+        without_type.scaling.x.factor = ScaleFactor::Float(2.0);
+
+        let mut mismatched = pass(1, None);
+        mismatched.scaling.valid = true;
+        mismatched.scaling.x.scale_type = ScaleType::Absolute;
+        mismatched.scaling.y.factor = ScaleFactor::Absolute(0);
+
+        let preset = preset(2, vec![without_type, mismatched]);
+        assert_eq!(
+            vec![
+                PresetValidationError::ScaleWithoutType(0),
+                PresetValidationError::ScaleFactorMismatch {
+                    pass: 1,
+                    axis: ScaleAxis::X,
+                },
+                PresetValidationError::ScaleFactorMismatch {
+                    pass: 1,
+                    axis: ScaleAxis::Y,
+                },
+                PresetValidationError::InvalidScaleFactor {
+                    pass: 1,
+                    axis: ScaleAxis::Y,
+                },
+            ],
+            preset.validate()
+        );
+    }
+
+    #[test]
+    fn finds_undeclared_parameters() {
+        let mut preset = preset(1, vec![pass(0, None)]);
+        preset.parameters.push(ParameterConfig {
+            name: "Declared".into(),
+            value: 1.0,
+        });
+        preset.parameters.push(ParameterConfig {
+            name: "Undeclared".into(),
+            value: 1.0,
+        });
+        assert_eq!(
+            vec![PresetValidationError::UndeclaredParameter(
+                "Undeclared".into()
+            )],
+            preset.validate_parameters(["Declared"])
+        );
+    }
+}
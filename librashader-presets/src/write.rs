@@ -0,0 +1,331 @@
+use crate::builtin::BuiltinShader;
+use crate::fs::{absolute_path, relative_path};
+use crate::{ParsePresetError, Scale2D, ScaleFactor, ShaderPreset};
+use librashader_common::{FilterMode, WrapMode};
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+impl ShaderPreset {
+    /// Serialize the shader preset into the contents of a `.slangp` file.
+    ///
+    /// Paths to shaders and textures are written relative to `root`, which should be
+    /// the directory the preset will be saved to. Paths that can not be made relative
+    /// to `root` (for example, if they are on a different drive) are written as is.
+    pub fn to_slangp_string(&self, root: impl AsRef<Path>) -> String {
+        let root = absolute_path(root.as_ref());
+        let mut out = String::new();
+
+        // NOPANIC: writing to a String is infallible.
+        write_preset(self, &root, &mut out).unwrap();
+        out
+    }
+
+    /// Write the shader preset in `.slangp` format to the given writer, with paths
+    /// relative to `root`.
+    pub fn write_to(&self, root: impl AsRef<Path>, mut writer: impl Write) -> std::io::Result<()> {
+        writer.write_all(self.to_slangp_string(root).as_bytes())
+    }
+
+    /// Save the shader preset as a `.slangp` file at the given path.
+    ///
+    /// Paths to shaders and textures are written relative to the directory the preset is
+    /// saved to.
+    pub fn save(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let path = absolute_path(path.as_ref());
+        let root = path.parent().unwrap_or(&path);
+        self.write_to(root, File::create(&path)?)
+    }
+
+    /// Serialize a "simple" override preset for this preset into the contents of a `.slangp` file.
+    ///
+    /// The simple preset references the preset at `base_path`, which this preset should have
+    /// been parsed from, and only sets the given parameters that differ from the values set in
+    /// this preset. Paths are written relative to `root`, which should be the directory the
+    /// simple preset will be saved to.
+    pub fn to_simple_slangp_string<S: AsRef<str>>(
+        &self,
+        base_path: impl AsRef<Path>,
+        parameters: impl IntoIterator<Item = (S, f32)>,
+        root: impl AsRef<Path>,
+    ) -> String {
+        let root = absolute_path(root.as_ref());
+        let base_path = absolute_path(base_path.as_ref());
+
+        let mut changed: Vec<(S, f32)> = parameters
+            .into_iter()
+            .filter(|(name, value)| {
+                !self
+                    .parameters
+                    .iter()
+                    .any(|base| base.name == name.as_ref() && base.value == *value)
+            })
+            .collect();
+        changed.sort_by(|(a, _), (b, _)| a.as_ref().cmp(b.as_ref()));
+
+        let mut out = String::new();
+        // NOPANIC: writing to a String is infallible.
+        writeln!(
+            out,
+            "#reference \"{}\"",
+            relative_path(&root, &base_path).display()
+        )
+        .unwrap();
+        for (name, value) in changed {
+            writeln!(out, "{} = \"{value}\"", name.as_ref()).unwrap();
+        }
+        out
+    }
+
+    /// Save a "simple" override preset at `path`, that references the preset at `base_path`
+    /// and only sets the given parameters that differ from the base preset.
+    ///
+    /// This is the same as RetroArch's "save simple preset". The base preset is parsed to find
+    /// which parameters differ from it.
+    pub fn save_simple_override<S: AsRef<str>>(
+        path: impl AsRef<Path>,
+        base_path: impl AsRef<Path>,
+        parameters: impl IntoIterator<Item = (S, f32)>,
+    ) -> Result<(), ParsePresetError> {
+        let path = absolute_path(path.as_ref());
+        let base_path = base_path.as_ref();
+        let base = ShaderPreset::try_parse(base_path)?;
+
+        let root = path.parent().unwrap_or(&path);
+        let contents = base.to_simple_slangp_string(base_path, parameters, root);
+        std::fs::write(&path, contents).map_err(|e| ParsePresetError::IOError(path.clone(), e))
+    }
+}
+
+fn wrap_mode_str(wrap_mode: WrapMode) -> &'static str {
+    match wrap_mode {
+        WrapMode::ClampToBorder => "clamp_to_border",
+        WrapMode::ClampToEdge => "clamp_to_edge",
+        WrapMode::Repeat => "repeat",
+        WrapMode::MirroredRepeat => "mirrored_repeat",
+    }
+}
+
+/// The path to write for a shader. Built-in shaders are written as is.
+fn shader_path(root: &Path, path: &Path) -> PathBuf {
+    if BuiltinShader::from_path(path).is_some() {
+        path.to_path_buf()
+    } else {
+        relative_path(root, path)
+    }
+}
+
+fn write_preset(preset: &ShaderPreset, root: &Path, out: &mut String) -> std::fmt::Result {
+    writeln!(out, "shaders = \"{}\"", preset.shader_count)?;
+    #[cfg(feature = "parse_legacy_glsl")]
+    writeln!(out, "feedback_pass = \"{}\"", preset.feedback_pass)?;
+
+    for shader in &preset.shaders {
+        let id = shader.id;
+        writeln!(out)?;
+        writeln!(
+            out,
+            "shader{id} = \"{}\"",
+            shader_path(root, &shader.name).display()
+        )?;
+
+        if let Some(alias) = &shader.alias {
+            writeln!(out, "alias{id} = \"{alias}\"")?;
+        }
+
+        writeln!(
+            out,
+            "filter_linear{id} = \"{}\"",
+            shader.filter == FilterMode::Linear
+        )?;
+        writeln!(
+            out,
+            "wrap_mode{id} = \"{}\"",
+            wrap_mode_str(shader.wrap_mode)
+        )?;
+        if shader.frame_count_mod != 0 {
+            writeln!(out, "frame_count_mod{id} = \"{}\"", shader.frame_count_mod)?;
+        }
+        writeln!(out, "mipmap_input{id} = \"{}\"", shader.mipmap_input)?;
+        if !shader.defines.is_empty() {
+            let defines: Vec<String> = shader.defines.iter().map(ToString::to_string).collect();
+            writeln!(out, "defines{id} = \"{}\"", defines.join(";"))?;
+        }
+        writeln!(
+            out,
+            "float_framebuffer{id} = \"{}\"",
+            shader.float_framebuffer
+        )?;
+        writeln!(
+            out,
+            "srgb_framebuffer{id} = \"{}\"",
+            shader.srgb_framebuffer
+        )?;
+        write_scaling(id, &shader.scaling, out)?;
+    }
+
+    if !preset.textures.is_empty() {
+        writeln!(out)?;
+        let names: Vec<&str> = preset.textures.iter().map(|t| t.name.as_str()).collect();
+        writeln!(out, "textures = \"{}\"", names.join(";"))?;
+        for texture in &preset.textures {
+            let name = &texture.name;
+            writeln!(
+                out,
+                "{name} = \"{}\"",
+                relative_path(root, &texture.path).display()
+            )?;
+            writeln!(
+                out,
+                "{name}_linear = \"{}\"",
+                texture.filter_mode == FilterMode::Linear
+            )?;
+            writeln!(
+                out,
+                "{name}_wrap_mode = \"{}\"",
+                wrap_mode_str(texture.wrap_mode)
+            )?;
+            writeln!(out, "{name}_mipmap = \"{}\"", texture.mipmap)?;
+        }
+    }
+
+    if !preset.parameters.is_empty() {
+        writeln!(out)?;
+        let names: Vec<&str> = preset.parameters.iter().map(|p| p.name.as_str()).collect();
+        writeln!(out, "parameters = \"{}\"", names.join(";"))?;
+        for parameter in &preset.parameters {
+            writeln!(out, "{} = \"{}\"", parameter.name, parameter.value)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn write_scaling(id: i32, scaling: &Scale2D, out: &mut String) -> std::fmt::Result {
+    if !scaling.valid {
+        // Without a scale type, the parser will only ever read float factors.
+        for (key, factor) in [("scale_x", scaling.x.factor), ("scale_y", scaling.y.factor)] {
+            if factor != ScaleFactor::default() {
+                writeln!(out, "{key}{id} = \"{}\"", f32::from(factor))?;
+            }
+        }
+        return Ok(());
+    }
+
+    // The parser decides whether a scale factor is absolute or not by looking at the
+    // scale type of the same axis, so the scale types must be written in a way that
+    // matches the factors written after.
+    let same_type = scaling.x.scale_type == scaling.y.scale_type;
+    if same_type {
+        writeln!(out, "scale_type{id} = \"{}\"", scaling.x.scale_type)?;
+    } else {
+        writeln!(out, "scale_type_x{id} = \"{}\"", scaling.x.scale_type)?;
+        writeln!(out, "scale_type_y{id} = \"{}\"", scaling.y.scale_type)?;
+    }
+
+    if same_type && scaling.x.factor == scaling.y.factor {
+        writeln!(out, "scale{id} = \"{}\"", scaling.x.factor)?;
+    } else {
+        writeln!(out, "scale_x{id} = \"{}\"", scaling.x.factor)?;
+        writeln!(out, "scale_y{id} = \"{}\"", scaling.y.factor)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{ParameterConfig, ScaleFactor, ScaleType, ShaderPreset, TextureConfig};
+    use librashader_common::{FilterMode, WrapMode};
+    use std::path::{Path, PathBuf};
+
+    fn copy_test_files(name: &str, files: &[&str]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("librashader-presets-{name}"));
+        std::fs::create_dir_all(&dir).unwrap();
+        for file in files {
+            std::fs::copy(Path::new("../test").join(file), dir.join(file)).unwrap();
+        }
+        dir.canonicalize().unwrap()
+    }
+
+    #[test]
+    fn round_trips_basic() {
+        let dir = copy_test_files("write-basic", &["basic.slangp", "basic.slang"]);
+        let preset = ShaderPreset::try_parse(dir.join("basic.slangp")).unwrap();
+
+        let out = dir.join("basic-written.slangp");
+        preset.save(&out).unwrap();
+        let written = ShaderPreset::try_parse(&out).unwrap();
+        assert_eq!(preset, written);
+    }
+
+    #[test]
+    fn round_trips_textures_and_scaling() {
+        let dir = copy_test_files("write-full", &["basic.slangp", "basic.slang", "sf2.png"]);
+        let mut preset = ShaderPreset::try_parse(dir.join("basic.slangp")).unwrap();
+
+        let mut second = preset.shaders[0].clone();
+        second.id = 1;
+        second.alias = Some("SecondPass".into());
+        second.filter = FilterMode::Linear;
+        second.frame_count_mod = 4;
+        second.scaling.valid = true;
+        second.scaling.x.scale_type = ScaleType::Absolute;
+        second.scaling.x.factor = ScaleFactor::Absolute(320);
+        second.scaling.y.scale_type = ScaleType::Viewport;
+        second.scaling.y.factor = ScaleFactor::Float(0.5);
+        preset.shaders.push(second);
+        preset.shader_count = 2;
+
+        preset.textures.push(TextureConfig {
+            name: "SamplerLUT".into(),
+            path: dir.join("sf2.png"),
+            wrap_mode: WrapMode::Repeat,
+            filter_mode: FilterMode::Linear,
+            mipmap: true,
+        });
+        preset.parameters.push(ParameterConfig {
+            name: "ColorMod2".into(),
+            value: -0.25,
+        });
+
+        let out = dir.join("full-written.slangp");
+        preset.save(&out).unwrap();
+        let written = ShaderPreset::try_parse(&out).unwrap();
+        assert_eq!(preset, written);
+    }
+
+    #[test]
+    fn saves_simple_override() {
+        let dir = copy_test_files("write-simple", &["basic.slangp", "basic.slang"]);
+        let base = ShaderPreset::try_parse(dir.join("basic.slangp")).unwrap();
+        let unchanged = base.parameters[0].clone();
+
+        let overrides = dir.join("overrides");
+        std::fs::create_dir_all(&overrides).unwrap();
+        let out = overrides.join("simple.slangp");
+        ShaderPreset::save_simple_override(
+            &out,
+            dir.join("basic.slangp"),
+            [
+                (unchanged.name.as_str(), unchanged.value),
+                ("ColorMod2", 0.25),
+            ],
+        )
+        .unwrap();
+
+        let written = std::fs::read_to_string(&out).unwrap();
+        assert_eq!(
+            "#reference \"../basic.slangp\"\nColorMod2 = \"0.25\"\n",
+            written
+        );
+
+        let simple = ShaderPreset::try_parse(&out).unwrap();
+        assert_eq!(base.shaders, simple.shaders);
+        assert!(simple
+            .parameters
+            .iter()
+            .any(|p| p.name == "ColorMod2" && p.value == 0.25));
+    }
+}
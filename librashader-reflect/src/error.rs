@@ -1,3 +1,4 @@
+use crate::front::ShaderDiagnostic;
 use crate::reflect::semantics::UniformMemberBlock;
 use thiserror::Error;
 
@@ -11,9 +12,25 @@ pub enum ShaderCompileError {
     NagaCompileError(Vec<naga::front::glsl::Error>),
 
     /// Compilation error from glslang.
+    ///
+    /// Shader sources that glslang fails to compile are reported as
+    /// [`ShaderCompileError::GlslangDiagnostics`] instead, along with their diagnostics.
     #[error("error when compiling with glslang: {0}")]
     GlslangError(#[from] glslang::error::GlslangError),
 
+    /// Compilation error from glslang, with the diagnostics reported by glslang.
+    ///
+    /// Returned whenever glslang fails to compile a shader source. Diagnostics are traced back
+    /// to the files and lines they were written on with the source maps of the shader source.
+    #[error("error when compiling with glslang: {error}")]
+    GlslangDiagnostics {
+        /// The error from glslang.
+        #[source]
+        error: glslang::error::GlslangError,
+        /// Every diagnostic reported by the stages up to the one that failed, including warnings.
+        diagnostics: Vec<ShaderDiagnostic>,
+    },
+
     /// Error when initializing the glslang compiler.
     #[error("error when initializing glslang")]
     CompilerInitError,
@@ -48,6 +65,16 @@ pub enum ShaderCompileError {
     NagaValidationError(#[from] naga::WithSpan<naga::valid::ValidationError>),
 }
 
+impl ShaderCompileError {
+    /// The diagnostics reported by the shader compiler, if any.
+    pub fn diagnostics(&self) -> &[ShaderDiagnostic] {
+        match self {
+            ShaderCompileError::GlslangDiagnostics { diagnostics, .. } => diagnostics,
+            _ => &[],
+        }
+    }
+}
+
 /// The error kind encountered when reflecting shader semantics.
 #[derive(Debug)]
 pub enum SemanticsErrorKind {
@@ -0,0 +1,198 @@
+use librashader_preprocess::SourceMap;
+use std::fmt::{Display, Formatter};
+use std::path::PathBuf;
+
+/// The stage of a shader that a diagnostic was reported for.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum ShaderStage {
+    /// The vertex shader.
+    Vertex,
+    /// The fragment shader.
+    Fragment,
+}
+
+/// The severity of a shader compiler diagnostic.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum DiagnosticSeverity {
+    /// The shader could not be compiled.
+    Error,
+    /// The shader was compiled, but may not behave as intended.
+    Warning,
+    /// Additional information about the shader.
+    Note,
+}
+
+/// A message reported by a shader compiler, such as an error or a warning.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShaderDiagnostic {
+    /// The stage the diagnostic was reported for.
+    pub stage: ShaderStage,
+    /// The severity of the diagnostic.
+    pub severity: DiagnosticSeverity,
+    /// The file the diagnostic was reported in, if it could be traced back to one.
+    pub file: Option<PathBuf>,
+    /// The line the diagnostic was reported on, starting from 1.
+    ///
+    /// If `file` is set, this is the line in that file. Otherwise, it is the line in the
+    /// preprocessed source of the stage.
+    pub line: Option<usize>,
+    /// The column the diagnostic was reported on, starting from 1, if the compiler reports it.
+    pub column: Option<usize>,
+    /// The message of the diagnostic.
+    pub message: String,
+}
+
+impl Display for ShaderDiagnostic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match &self.file {
+            Some(file) => write!(f, "{}:", file.display())?,
+            None => write!(f, "{:?} shader:", self.stage)?,
+        }
+        if let Some(line) = self.line {
+            write!(f, "{line}:")?;
+        }
+        if let Some(column) = self.column {
+            write!(f, "{column}:")?;
+        }
+        let severity = match self.severity {
+            DiagnosticSeverity::Error => "error",
+            DiagnosticSeverity::Warning => "warning",
+            DiagnosticSeverity::Note => "note",
+        };
+        write!(f, " {severity}: {}", self.message)
+    }
+}
+
+/// Parse the info log of glslang into diagnostics.
+///
+/// Lines reported against the source string are traced back to their files with `map`, if
+/// given. Diagnostics on or after the line `end` are skipped.
+pub(crate) fn parse_glslang_log(
+    log: &str,
+    stage: ShaderStage,
+    map: Option<&SourceMap>,
+    end: Option<usize>,
+) -> Vec<ShaderDiagnostic> {
+    let mut diagnostics: Vec<ShaderDiagnostic> = Vec::new();
+    for line in log.lines() {
+        let Some(diagnostic) = parse_glslang_line(line, stage, map, end) else {
+            continue;
+        };
+        // glslang reports preprocessor warnings once when preprocessing, and again when parsing.
+        if !diagnostics.contains(&diagnostic) {
+            diagnostics.push(diagnostic);
+        }
+    }
+    diagnostics
+}
+
+fn parse_glslang_line(
+    line: &str,
+    stage: ShaderStage,
+    map: Option<&SourceMap>,
+    end: Option<usize>,
+) -> Option<ShaderDiagnostic> {
+    let (severity, rest) = [
+        ("ERROR: ", DiagnosticSeverity::Error),
+        ("INTERNAL ERROR: ", DiagnosticSeverity::Error),
+        ("UNIMPLEMENTED: ", DiagnosticSeverity::Error),
+        ("WARNING: ", DiagnosticSeverity::Warning),
+        ("NOTE: ", DiagnosticSeverity::Note),
+    ]
+    .into_iter()
+    .find_map(|(prefix, severity)| line.strip_prefix(prefix).map(|rest| (severity, rest)))?;
+
+    // the summary at the end of the log, and the note that parsing stopped.
+    if rest.ends_with("compilation errors.  No code generated.")
+        || rest.trim_end().ends_with("'' : compilation terminated")
+    {
+        return None;
+    }
+
+    let mut diagnostic = ShaderDiagnostic {
+        stage,
+        severity,
+        file: None,
+        line: None,
+        column: None,
+        message: rest.trim().to_string(),
+    };
+
+    // locations are `string:line:` or `string:line:column:`, where `string` is the index of the
+    // source string, or the file name set by a `#line` directive.
+    let Some((location, message)) = rest.split_once(": ") else {
+        return Some(diagnostic);
+    };
+    let mut parts = location.split(':');
+    let (Some(string), Some(Ok(line))) = (parts.next(), parts.next().map(str::parse::<usize>))
+    else {
+        return Some(diagnostic);
+    };
+    let column = parts.next().and_then(|column| column.parse::<usize>().ok());
+
+    if end.is_some_and(|end| line >= end) {
+        return None;
+    }
+
+    diagnostic.message = message.trim().to_string();
+    diagnostic.column = column;
+    diagnostic.line = Some(line);
+    if string.parse::<usize>().is_err() {
+        diagnostic.file = Some(PathBuf::from(string));
+    } else if let Some((file, line)) = map.and_then(|map| map.lookup(line)) {
+        diagnostic.file = Some(file.to_path_buf());
+        diagnostic.line = Some(line);
+    }
+    Some(diagnostic)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::front::diagnostic::parse_glslang_log;
+    use crate::front::{DiagnosticSeverity, ShaderDiagnostic, ShaderStage};
+    use std::path::PathBuf;
+
+    #[test]
+    fn parses_glslang_log() {
+        let log = concat!(
+            "WARNING: 0:3: '#extension' : extension not supported: GL_FOO_bar\n",
+            "WARNING: 0:3: '#extension' : extension not supported: GL_FOO_bar\n",
+            "ERROR: basic.slang:5: 'nope' : undeclared identifier \n",
+            "ERROR: basic.slang:5: '' : compilation terminated \n",
+            "ERROR: 0:8: '' :  syntax error, unexpected IDENTIFIER\n",
+            "ERROR: Linking fragment stage: Missing entry point\n",
+            "ERROR: 2 compilation errors.  No code generated.\n",
+        );
+
+        let diagnostics = parse_glslang_log(log, ShaderStage::Fragment, None, Some(8));
+        assert_eq!(
+            vec![
+                ShaderDiagnostic {
+                    stage: ShaderStage::Fragment,
+                    severity: DiagnosticSeverity::Warning,
+                    file: None,
+                    line: Some(3),
+                    column: None,
+                    message: "'#extension' : extension not supported: GL_FOO_bar".to_string(),
+                },
+                ShaderDiagnostic {
+                    stage: ShaderStage::Fragment,
+                    severity: DiagnosticSeverity::Error,
+                    file: Some(PathBuf::from("basic.slang")),
+                    line: Some(5),
+                    column: None,
+                    message: "'nope' : undeclared identifier".to_string(),
+                },
+                ShaderDiagnostic {
+                    stage: ShaderStage::Fragment,
+                    severity: DiagnosticSeverity::Error,
+                    file: None,
+                    line: None,
+                    column: None,
+                    message: "Linking fragment stage: Missing entry point".to_string(),
+                },
+            ],
+            diagnostics
+        );
+    }
+}
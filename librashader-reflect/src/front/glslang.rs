@@ -1,11 +1,15 @@
 use crate::error::ShaderCompileError;
+use glslang::error::GlslangError;
 use glslang::{CompilerOptions, ShaderInput, ShaderMessage};
-use librashader_preprocess::ShaderSource;
+use librashader_preprocess::{ShaderSource, SourceMap};
 use rspirv::binary::Assemble;
 use rspirv::dr::Builder;
 
+use crate::front::diagnostic::parse_glslang_log;
 use crate::front::spirv_passes::{link_input_outputs, load_module};
-use crate::front::{ShaderInputCompiler, SpirvCompilation};
+use crate::front::{
+    DiagnosticSeverity, ShaderDiagnostic, ShaderInputCompiler, ShaderStage, SpirvCompilation,
+};
 
 /// glslang compiler
 pub struct Glslang;
@@ -16,9 +20,31 @@ impl ShaderInputCompiler<SpirvCompilation> for Glslang {
     }
 }
 
-pub(crate) fn compile_spirv(source: &ShaderSource) -> Result<SpirvCompilation, ShaderCompileError> {
-    let compiler = glslang::Compiler::acquire().ok_or(ShaderCompileError::CompilerInitError)?;
-    let options = CompilerOptions {
+impl Glslang {
+    /// Compile the shader source, reporting every diagnostic from glslang, including the
+    /// warnings of a successful compile.
+    ///
+    /// Diagnostics are traced back to the files and lines they were written on with the source
+    /// maps of the shader source. If compilation fails, the diagnostics are returned in
+    /// [`ShaderCompileError::GlslangDiagnostics`].
+    pub fn compile_with_diagnostics(
+        source: &ShaderSource,
+    ) -> Result<(SpirvCompilation, Vec<ShaderDiagnostic>), ShaderCompileError> {
+        compile_stages(source, true)
+    }
+}
+
+impl From<ShaderStage> for glslang::ShaderStage {
+    fn from(stage: ShaderStage) -> Self {
+        match stage {
+            ShaderStage::Vertex => glslang::ShaderStage::Vertex,
+            ShaderStage::Fragment => glslang::ShaderStage::Fragment,
+        }
+    }
+}
+
+fn compiler_options() -> CompilerOptions {
+    CompilerOptions {
         source_language: glslang::SourceLanguage::GLSL,
         target: glslang::Target::Vulkan {
             version: glslang::VulkanVersion::Vulkan1_0,
@@ -26,19 +52,128 @@ pub(crate) fn compile_spirv(source: &ShaderSource) -> Result<SpirvCompilation, S
         },
         version_profile: None,
         messages: ShaderMessage::DEFAULT,
+    }
+}
+
+fn compile_stage(
+    compiler: &glslang::Compiler,
+    options: &CompilerOptions,
+    text: &str,
+    stage: ShaderStage,
+) -> Result<Vec<u32>, GlslangError> {
+    let source = glslang::ShaderSource::from(text);
+    let input = ShaderInput::new(&source, stage.into(), options, None)?;
+    let shader = compiler.create_shader(input)?;
+    shader.compile()
+}
+
+/// Collect the diagnostics glslang reports when parsing a stage.
+///
+/// glslang only returns its info log when parsing fails, so to get the warnings of a
+/// stage that parses, it is parsed again with a syntax error after its last line, and the
+/// error is left out. `#line` directives are blanked so that lines are reported as lines
+/// of the stage, which can be looked up in its source map.
+fn stage_diagnostics(
+    compiler: &glslang::Compiler,
+    options: &CompilerOptions,
+    text: &str,
+    map: &SourceMap,
+    stage: ShaderStage,
+) -> Vec<ShaderDiagnostic> {
+    let mut source = String::with_capacity(text.len());
+    let mut end = 1;
+    for line in text.lines() {
+        if !line.starts_with("#line ") {
+            source.push_str(line);
+        }
+        source.push('\n');
+        end += 1;
+    }
+    source.push_str("librashader_end_of_diagnostics\n");
+
+    let source = glslang::ShaderSource::from(source);
+    let Ok(input) = ShaderInput::new(&source, stage.into(), options, None) else {
+        return Vec::new();
     };
+    match compiler.create_shader(input) {
+        Err(GlslangError::PreprocessError(log) | GlslangError::ParseError(log)) => {
+            parse_glslang_log(&log, stage, Some(map), Some(end))
+        }
+        _ => Vec::new(),
+    }
+}
+
+fn error_log(error: &GlslangError) -> Option<&str> {
+    match error {
+        GlslangError::PreprocessError(log)
+        | GlslangError::ParseError(log)
+        | GlslangError::MapIoError(log)
+        | GlslangError::LinkError(log) => Some(log),
+        _ => None,
+    }
+}
 
-    let vertex = glslang::ShaderSource::from(source.vertex.as_str());
-    let vertex = ShaderInput::new(&vertex, glslang::ShaderStage::Vertex, &options, None)?;
-    let vertex = compiler.create_shader(vertex)?;
+pub(crate) fn compile_spirv(source: &ShaderSource) -> Result<SpirvCompilation, ShaderCompileError> {
+    compile_stages(source, false).map(|(compilation, _)| compilation)
+}
 
-    let fragment = glslang::ShaderSource::from(source.fragment.as_str());
-    let fragment = ShaderInput::new(&fragment, glslang::ShaderStage::Fragment, &options, None)?;
-    let fragment = compiler.create_shader(fragment)?;
+/// Compile both stages of the shader source.
+///
+/// Diagnostics need each stage to be parsed again, so unless `warnings` is set, they are
+/// only collected once a stage fails to compile.
+fn compile_stages(
+    source: &ShaderSource,
+    warnings: bool,
+) -> Result<(SpirvCompilation, Vec<ShaderDiagnostic>), ShaderCompileError> {
+    let compiler = glslang::Compiler::acquire().ok_or(ShaderCompileError::CompilerInitError)?;
+    let options = compiler_options();
 
-    let vertex = vertex.compile()?;
-    let fragment = fragment.compile()?;
+    let stages = [
+        (&source.vertex, &source.vertex_map, ShaderStage::Vertex),
+        (
+            &source.fragment,
+            &source.fragment_map,
+            ShaderStage::Fragment,
+        ),
+    ];
+    let mut diagnostics = Vec::new();
+    let mut compiled = Vec::new();
+    for (index, &(text, map, stage)) in stages.iter().enumerate() {
+        if warnings {
+            diagnostics.extend(stage_diagnostics(compiler, &options, text, map, stage));
+        }
+        let error = match compile_stage(compiler, &options, text, stage) {
+            Ok(spirv) => {
+                compiled.push(spirv);
+                continue;
+            }
+            Err(error) => error,
+        };
 
+        if !warnings {
+            for &(text, map, stage) in &stages[..=index] {
+                diagnostics.extend(stage_diagnostics(compiler, &options, text, map, stage));
+            }
+        }
+        // errors that happen after parsing, such as when linking, are only in the
+        // log of the failed compile.
+        let reported = diagnostics.iter().any(|diagnostic| {
+            diagnostic.stage == stage && diagnostic.severity == DiagnosticSeverity::Error
+        });
+        if let (false, Some(log)) = (reported, error_log(&error)) {
+            diagnostics.extend(parse_glslang_log(log, stage, None, None));
+        }
+        return Err(ShaderCompileError::GlslangDiagnostics { error, diagnostics });
+    }
+
+    let fragment = compiled.pop();
+    let vertex = compiled.pop();
+    // NOPANIC: both stages were compiled if the loop finished.
+    let compilation = link_stages(vertex.unwrap(), fragment.unwrap());
+    Ok((compilation, diagnostics))
+}
+
+fn link_stages(vertex: Vec<u32>, fragment: Vec<u32>) -> SpirvCompilation {
     let vertex = load_module(&vertex);
     let fragment = load_module(&fragment);
     let mut fragment = Builder::new_from_module(fragment);
@@ -50,16 +185,25 @@ pub(crate) fn compile_spirv(source: &ShaderSource) -> Result<SpirvCompilation, S
     let vertex = vertex.module().assemble();
     let fragment = fragment.module().assemble();
 
-    Ok(SpirvCompilation { vertex, fragment })
+    SpirvCompilation { vertex, fragment }
 }
 
 #[cfg(test)]
 mod test {
     use crate::front::glslang::compile_spirv;
     use librashader_preprocess::ShaderSource;
+    use librashader_presets::BuiltinShader;
     #[test]
     pub fn compile_shader() {
         let result = ShaderSource::load("../test/basic.slang").unwrap();
         let _spirv = compile_spirv(&result).unwrap();
     }
+
+    #[test]
+    pub fn compile_builtin_shaders() {
+        for shader in BuiltinShader::ALL {
+            let result = ShaderSource::load(shader.path()).unwrap();
+            compile_spirv(&result).unwrap();
+        }
+    }
 }
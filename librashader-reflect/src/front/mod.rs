@@ -3,6 +3,7 @@ use librashader_preprocess::ShaderSource;
 use serde::{Deserialize, Serialize};
 pub(crate) mod spirv_passes;
 
+mod diagnostic;
 mod glslang;
 
 /// The output of a shader compiler that is reflectable.
@@ -11,6 +12,7 @@ pub trait ShaderReflectObject: Sized {
     type Compiler;
 }
 
+pub use crate::front::diagnostic::{DiagnosticSeverity, ShaderDiagnostic, ShaderStage};
 pub use crate::front::glslang::Glslang;
 
 /// Trait for types that can compile shader sources into a compilation unit.
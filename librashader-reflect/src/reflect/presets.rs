@@ -7,6 +7,7 @@ use crate::reflect::semantics::{
 };
 use librashader_common::map::{FastHashMap, ShortString};
 use librashader_preprocess::{PreprocessError, ShaderSource};
+use librashader_presets::fs::DiskFileSystem;
 use librashader_presets::{ShaderPassConfig, TextureConfig};
 
 /// Artifacts of a reflected and compiled shader pass.
@@ -87,7 +88,8 @@ where
     let passes = passes
         .into_iter()
         .map(|shader| {
-            let source: ShaderSource = ShaderSource::load(&shader.name)?;
+            let source: ShaderSource =
+                ShaderSource::load_with_defines(&shader.name, &DiskFileSystem, &shader.defines)?;
 
             let compiled = I::Compiler::compile(&source)?;
             let reflect = T::from_compilation(compiled)?;
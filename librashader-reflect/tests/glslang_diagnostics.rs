@@ -0,0 +1,90 @@
+use librashader_preprocess::ShaderSource;
+use librashader_presets::fs::MemoryFileSystem;
+use librashader_reflect::error::ShaderCompileError;
+use librashader_reflect::front::{
+    DiagnosticSeverity, Glslang, ShaderDiagnostic, ShaderInputCompiler, ShaderStage,
+};
+use std::path::{Path, PathBuf};
+
+const SHADER: &str = r#"#version 450
+#include "common.inc"
+#extension GL_FOO_bar : warn
+
+#pragma stage vertex
+layout(location = 0) in vec4 Position;
+void main() { gl_Position = Position; }
+
+#pragma stage fragment
+layout(location = 0) out vec4 FragColor;
+void main() { FragColor = vec4(SCALE); }
+"#;
+
+fn load(common: &str) -> ShaderSource {
+    let mut fs = MemoryFileSystem::new();
+    fs.insert("/shaders/diagnostics.slang", SHADER);
+    fs.insert("/shaders/common.inc", common);
+    ShaderSource::load_with_resolver("/shaders/diagnostics.slang", &fs).unwrap()
+}
+
+fn locations(
+    diagnostics: &[ShaderDiagnostic],
+) -> Vec<(ShaderStage, DiagnosticSeverity, &Path, usize)> {
+    diagnostics
+        .iter()
+        .map(|d| {
+            (
+                d.stage,
+                d.severity,
+                d.file.as_deref().unwrap(),
+                d.line.unwrap(),
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn reports_warnings_of_successful_compile() {
+    let source = load("\n#define SCALE 1.0\n");
+    let (_, diagnostics) = Glslang::compile_with_diagnostics(&source).unwrap();
+
+    let shader = Path::new("/shaders/diagnostics.slang");
+    assert_eq!(
+        vec![
+            (ShaderStage::Vertex, DiagnosticSeverity::Warning, shader, 3),
+            (
+                ShaderStage::Fragment,
+                DiagnosticSeverity::Warning,
+                shader,
+                3
+            ),
+        ],
+        locations(&diagnostics)
+    );
+    assert!(diagnostics[0].message.contains("GL_FOO_bar"));
+}
+
+#[test]
+fn reports_errors_in_included_files() {
+    let source = load("\n#define SCALE 1.0\nconst float OFFSET = missing;\n");
+
+    // the error is the same whether or not warnings are collected.
+    for error in [
+        Glslang::compile_with_diagnostics(&source).unwrap_err(),
+        Glslang::compile(&source).unwrap_err(),
+    ] {
+        assert!(matches!(
+            error,
+            ShaderCompileError::GlslangDiagnostics { .. }
+        ));
+        let errors: Vec<_> = locations(error.diagnostics())
+            .into_iter()
+            .filter(|(_, severity, _, _)| *severity == DiagnosticSeverity::Error)
+            .collect();
+        assert!(!errors.is_empty());
+        for (stage, _, file, line) in errors {
+            assert_eq!(ShaderStage::Vertex, stage);
+            assert_eq!(PathBuf::from("/shaders/common.inc"), file);
+            assert_eq!(3, line);
+        }
+    }
+}
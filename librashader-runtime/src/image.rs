@@ -1,5 +1,7 @@
 pub use image::ImageError;
 use librashader_common::Size;
+use librashader_presets::fs::DiskFileSystem;
+use librashader_presets::PresetFileSystem;
 use std::marker::PhantomData;
 
 use std::path::Path;
@@ -65,8 +67,16 @@ pub enum UVDirection {
 
 impl<P: PixelFormat> Image<P> {
     /// Load the image from the path as RGBA8.
+    ///
+    /// The image is read through [`DiskFileSystem`], so images within preset bundles
+    /// can be loaded when the `archive` feature of `librashader-presets` is enabled.
     pub fn load(path: impl AsRef<Path>, direction: UVDirection) -> Result<Self, ImageError> {
-        let mut image = image::open(path.as_ref())?;
+        let path = path.as_ref();
+        let bytes = DiskFileSystem.read(path).map_err(ImageError::IoError)?;
+        let mut image = match image::ImageFormat::from_path(path) {
+            Ok(format) => image::load_from_memory_with_format(&bytes, format)?,
+            Err(_) => image::load_from_memory(&bytes)?,
+        };
 
         if direction == UVDirection::BottomLeft {
             image = image.flipv();
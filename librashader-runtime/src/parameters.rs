@@ -1,6 +1,7 @@
 use arc_swap::ArcSwap;
 use librashader_common::map::{FastHashMap, ShortString};
-use librashader_presets::ParameterConfig;
+use librashader_presets::{ParameterConfig, ParsePresetError, ShaderPreset};
+use std::path::Path;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 
@@ -62,6 +63,24 @@ impl RuntimeParameters {
         self.parameters.load_full()
     }
 
+    /// Save the current parameter values as a "simple" override preset at `path`.
+    ///
+    /// The written preset references the preset at `base_path`, and only sets the parameters
+    /// whose current values differ from the base preset.
+    /// See [`ShaderPreset::save_simple_override`].
+    pub fn save_simple_override(
+        &self,
+        path: impl AsRef<Path>,
+        base_path: impl AsRef<Path>,
+    ) -> Result<(), ParsePresetError> {
+        let parameters = self.parameters.load();
+        ShaderPreset::save_simple_override(
+            path,
+            base_path,
+            parameters.iter().map(|(name, value)| (name, *value)),
+        )
+    }
+
     /// Get the number of passes enabled.
     ///
     /// If set from [`RuntimeParameters::set_passes_enabled`] from a different thread,
@@ -70,6 +70,10 @@ pub mod presets {
     use librashader_preprocess::{PreprocessError, ShaderParameter, ShaderSource};
     pub use librashader_presets::*;
     /// Get full parameter metadata from a shader preset.
+    ///
+    /// Parameters are returned in no particular order, once for every pass that declares them.
+    /// See [`preset_parameters`](librashader_preprocess::preset_parameters) to get parameters
+    /// in declaration order, without duplicates.
     pub fn get_parameter_meta(
         preset: &ShaderPreset,
     ) -> Result<impl Iterator<Item = ShaderParameter>, PreprocessError> {